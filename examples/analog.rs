@@ -1,23 +1,11 @@
 extern crate firmata;
-extern crate serial;
 
 use firmata::*;
-use serial::*;
 use std::thread;
+use std::time::Duration;
 
 fn main() {
-    let mut sp = serial::open("/dev/ttyACM0").unwrap();
-
-    sp.reconfigure(&|settings| {
-        settings.set_baud_rate(Baud57600).unwrap();
-        settings.set_char_size(Bits8);
-        settings.set_parity(ParityNone);
-        settings.set_stop_bits(Stop1);
-        settings.set_flow_control(FlowNone);
-        Ok(())
-    }).unwrap();
-
-    let mut b = firmata::Board::new(Box::new(sp)).unwrap();
+    let mut b = firmata::Board::open("/dev/ttyACM0", 57600).unwrap();
 
     let pin = 14; // A0
 
@@ -25,13 +13,13 @@ fn main() {
     println!("firmware name {}", b.firmware_name());
     println!("protocol version {}", b.protocol_version());
 
-    b.set_pin_mode(pin, firmata::ANALOG);
+    b.set_pin_mode(pin, PinMode::Analog).unwrap();
 
-    b.report_analog(pin, 1);
+    b.enable_reporting(pin).unwrap();
 
     loop {
-        b.read_and_decode();
-        println!("analog value: {}", b.pins[pin as usize].value);
-        thread::sleep_ms(10);
+        b.read_and_decode().unwrap();
+        println!("analog value: {}", b.pins[pin as usize].reported_value);
+        thread::sleep(Duration::from_millis(10));
     }
 }
@@ -1,77 +1,39 @@
 extern crate firmata;
-extern crate serial;
 
 use firmata::*;
-use serial::*;
-use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::Duration;
 
-fn init<T: firmata::Firmata>(board: &Arc<Mutex<T>>) {
-    let mut b = board.lock().unwrap();
-    b.i2c_config(0);
-    b.i2c_write(0x09, "o".as_bytes());
-    thread::sleep_ms(10);
+fn init<T: Firmata>(b: &mut T) {
+    b.i2c_config(0).unwrap();
+    b.i2c_write(0x09, "o".as_bytes()).unwrap();
+    thread::sleep(Duration::from_millis(10));
 }
 
-fn set_rgb<T: firmata::Firmata>(board: &Arc<Mutex<T>>, rgb: [u8; 3]) {
-    let mut b = board.lock().unwrap();
-    b.i2c_write(0x09, "n".as_bytes());
-    b.i2c_write(0x09, &rgb);
+fn set_rgb<T: Firmata>(b: &mut T, rgb: [u8; 3]) {
+    b.i2c_write(0x09, "n".as_bytes()).unwrap();
+    b.i2c_write(0x09, &rgb).unwrap();
 }
 
-fn read_rgb<T: firmata::Firmata>(board: &Arc<Mutex<T>>) -> Vec<u8> {
-    {
-        let mut b = board.lock().unwrap();
-        b.i2c_write(0x09, "g".as_bytes());
-        b.i2c_read(0x09, 3);
-    }
-    loop {
-        {
-            let mut b = board.lock().unwrap();
-            if b.i2c_data().iter().count() > 0 {
-                return b.i2c_data().pop().unwrap().data;
-            }
-        }
-        thread::sleep_ms(10);
-    }
+fn read_rgb<T: Firmata>(b: &mut T) -> Vec<u8> {
+    b.i2c_write(0x09, "g".as_bytes()).unwrap();
+    b.i2c_read_reply(0x09, None, 3, Duration::from_secs(1)).unwrap().data
 }
 
 fn main() {
-    let mut sp = serial::open("/dev/ttyACM0").unwrap();
+    let mut board = firmata::Board::open("/dev/ttyACM0", 57600).unwrap();
 
-    sp.reconfigure(&|settings| {
-        settings.set_baud_rate(Baud57600).unwrap();
-        settings.set_char_size(Bits8);
-        settings.set_parity(ParityNone);
-        settings.set_stop_bits(Stop1);
-        settings.set_flow_control(FlowNone);
-        Ok(())
-    }).unwrap();
+    init(&mut board);
 
-    let board = Arc::new(Mutex::new(firmata::Board::new(Box::new(sp)).unwrap()));
+    set_rgb(&mut board, [255, 0, 0]);
+    println!("rgb: {:?}", read_rgb(&mut board));
+    thread::sleep(Duration::from_millis(1000));
 
-    {
-        let b = board.clone();
-        thread::spawn(move || {
-            loop {
-                b.lock().unwrap().read_and_decode();
-                b.lock().unwrap().query_firmware();
-                thread::sleep_ms(10);
-            }
-        });
-    }
+    set_rgb(&mut board, [0, 255, 0]);
+    println!("rgb: {:?}", read_rgb(&mut board));
+    thread::sleep(Duration::from_millis(1000));
 
-    init(&board);
-
-    set_rgb(&board, [255, 0, 0]);
-    println!("rgb: {:?}", read_rgb(&board));
-    thread::sleep_ms(1000);
-
-    set_rgb(&board, [0, 255, 0]);
-    println!("rgb: {:?}", read_rgb(&board));
-    thread::sleep_ms(1000);
-
-    set_rgb(&board, [0, 0, 255]);
-    println!("rgb: {:?}", read_rgb(&board));
-    thread::sleep_ms(1000);
+    set_rgb(&mut board, [0, 0, 255]);
+    println!("rgb: {:?}", read_rgb(&mut board));
+    thread::sleep(Duration::from_millis(1000));
 }
@@ -1,23 +1,11 @@
 extern crate firmata;
-extern crate serial;
 
 use firmata::*;
-use serial::*;
 use std::thread;
+use std::time::Duration;
 
 fn main() {
-    let mut sp = serial::open("/dev/ttyACM0").unwrap();
-
-    sp.reconfigure(&|settings| {
-        settings.set_baud_rate(Baud57600).unwrap();
-        settings.set_char_size(Bits8);
-        settings.set_parity(ParityNone);
-        settings.set_stop_bits(Stop1);
-        settings.set_flow_control(FlowNone);
-        Ok(())
-    }).unwrap();
-
-    let mut b = firmata::Board::new(Box::new(sp)).unwrap();
+    let mut b = firmata::Board::open("/dev/ttyACM0", 57600).unwrap();
 
     println!("firmware version {}", b.firmware_version());
     println!("firmware name {}", b.firmware_name());
@@ -26,21 +14,25 @@ fn main() {
     let led = 13;
     let button = 2;
 
-    b.set_pin_mode(led, firmata::OUTPUT);
-    b.set_pin_mode(button, firmata::INPUT);
+    b.set_pin_mode(led, PinMode::Output).unwrap();
+    b.set_pin_mode(button, PinMode::Input).unwrap();
 
-    b.report_digital(button, 1);
+    b.enable_reporting(button).unwrap();
 
     loop {
-        b.read_and_decode();
-        if b.pins()[button as usize].value == 0 {
+        // The board only reports the button when it changes.
+        match b.read_and_decode() {
+            Ok(_) | Err(Error::Timeout) => {},
+            Err(e) => panic!("{}", e),
+        }
+        if b.pins()[button as usize].reported_value == 0 {
             println!("off");
-            b.digital_write(led, 0);
+            b.digital_write(led, 0).unwrap();
         } else {
             println!("on");
-            b.digital_write(led, 1);
+            b.digital_write(led, 1).unwrap();
         }
 
-        thread::sleep_ms(100);
+        thread::sleep(Duration::from_millis(100));
     }
 }
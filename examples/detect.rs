@@ -0,0 +1,9 @@
+extern crate firmata;
+
+use std::time::Duration;
+
+fn main() {
+    for board in firmata::detect_boards(57600, Duration::from_secs(3)) {
+        println!("{} {}", board.path.display(), board.firmware_name);
+    }
+}
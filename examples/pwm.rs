@@ -1,23 +1,11 @@
 extern crate firmata;
-extern crate serial;
 
 use firmata::*;
-use serial::*;
 use std::thread;
+use std::time::Duration;
 
 fn main() {
-    let mut sp = serial::open("/dev/ttyACM0").unwrap();
-
-    sp.reconfigure(&|settings| {
-        settings.set_baud_rate(Baud57600).unwrap();
-        settings.set_char_size(Bits8);
-        settings.set_parity(ParityNone);
-        settings.set_stop_bits(Stop1);
-        settings.set_flow_control(FlowNone);
-        Ok(())
-    }).unwrap();
-
-    let mut b = firmata::Board::new(Box::new(sp)).unwrap();
+    let mut b = firmata::Board::open("/dev/ttyACM0", 57600).unwrap();
 
     let pin = 3;
 
@@ -25,13 +13,13 @@ fn main() {
     println!("firmware name {}", b.firmware_name());
     println!("protocol version {}", b.protocol_version());
 
-    b.set_pin_mode(pin, firmata::PWM);
+    b.set_pin_mode(pin, PinMode::Pwm).unwrap();
 
     loop {
         for value in 0..255 {
-            b.analog_write(pin, value);
+            b.analog_write(pin, value).unwrap();
             println!("{}", value);
-            thread::sleep_ms(10);
+            thread::sleep(Duration::from_millis(10));
         }
     }
 }
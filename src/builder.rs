@@ -0,0 +1,141 @@
+//! This module contains a builder for configuring how a `Board` is
+//! initialised.
+use std::io;
+use std::thread;
+use std::time::Duration;
+
+use {Board, Firmata, Result, DEFAULT_TIMEOUT};
+
+/// A builder that opens a `Board` with a configurable handshake.
+///
+/// The defaults match `Board::new`: firmware, capability and analog mapping
+/// queries are run and digital reporting is enabled on ports 0 and 1.
+#[derive(Debug, Clone)]
+pub struct BoardBuilder {
+    query_firmware: bool,
+    query_capabilities: bool,
+    query_analog_mapping: bool,
+    digital_ports: Vec<u8>,
+    analog_channels: Vec<u8>,
+    pins: Vec<i32>,
+    startup_delay: Duration,
+    sampling_interval: Option<Duration>,
+    timeout: Duration,
+}
+
+impl Default for BoardBuilder {
+    fn default() -> BoardBuilder {
+        BoardBuilder::new()
+    }
+}
+
+impl BoardBuilder {
+    /// Creates a new `BoardBuilder` with the default handshake.
+    pub fn new() -> BoardBuilder {
+        BoardBuilder {
+            query_firmware: true,
+            query_capabilities: true,
+            query_analog_mapping: true,
+            digital_ports: vec![0, 1],
+            analog_channels: vec![],
+            pins: vec![],
+            startup_delay: Duration::from_millis(0),
+            sampling_interval: None,
+            timeout: Duration::from_millis(DEFAULT_TIMEOUT),
+        }
+    }
+
+    /// Sets whether the firmware name and protocol version are queried.
+    pub fn query_firmware(mut self, enabled: bool) -> BoardBuilder {
+        self.query_firmware = enabled;
+        self
+    }
+
+    /// Sets whether the pin capabilities are queried. Without them every
+    /// pin-taking function fails with `Error::InvalidPin`.
+    pub fn query_capabilities(mut self, enabled: bool) -> BoardBuilder {
+        self.query_capabilities = enabled;
+        self
+    }
+
+    /// Sets whether the analog mapping is queried. Without it analog reports
+    /// cannot be matched to their pins.
+    pub fn query_analog_mapping(mut self, enabled: bool) -> BoardBuilder {
+        self.query_analog_mapping = enabled;
+        self
+    }
+
+    /// Sets the digital ports to enable reporting on.
+    pub fn report_digital(mut self, ports: &[u8]) -> BoardBuilder {
+        self.digital_ports = ports.to_vec();
+        self
+    }
+
+    /// Sets the analog channels to enable reporting on.
+    pub fn report_analog(mut self, channels: &[u8]) -> BoardBuilder {
+        self.analog_channels = channels.to_vec();
+        self
+    }
+
+    /// Sets the pins to enable reporting on with `Firmata::enable_reporting`,
+    /// which picks the analog channel or digital port of each pin. Needs the
+    /// capability and analog mapping queries.
+    pub fn report_pins(mut self, pins: &[i32]) -> BoardBuilder {
+        self.pins = pins.to_vec();
+        self
+    }
+
+    /// Sets how long to wait before talking to the board, for boards that
+    /// reset when the connection is opened.
+    pub fn startup_delay(mut self, delay: Duration) -> BoardBuilder {
+        self.startup_delay = delay;
+        self
+    }
+
+    /// Sets the sampling interval to configure once the handshake is done.
+    pub fn sampling_interval(mut self, interval: Duration) -> BoardBuilder {
+        self.sampling_interval = Some(interval);
+        self
+    }
+
+    /// Sets how long each handshake query waits for the board to answer.
+    /// The board keeps this as its query timeout.
+    pub fn timeout(mut self, timeout: Duration) -> BoardBuilder {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Creates a `Board` on `connection` and runs the configured handshake.
+    pub fn build<T: io::Read+io::Write>(&self, connection: Box<T>) -> Result<Board<T>> {
+        let mut b = Board::from_connection(connection);
+        b.set_timeout(self.timeout);
+
+        if self.startup_delay > Duration::from_millis(0) {
+            thread::sleep(self.startup_delay);
+        }
+
+        if self.query_firmware {
+            b.query_firmware()?;
+        }
+        if self.query_capabilities {
+            b.query_capabilities()?;
+        }
+        if self.query_analog_mapping {
+            b.query_analog_mapping()?;
+        }
+        if let Some(interval) = self.sampling_interval {
+            b.set_sampling_interval(interval)?;
+        }
+        for port in &self.digital_ports {
+            b.report_digital(*port as i32, 1)?;
+        }
+        for channel in &self.analog_channels {
+            b.report_analog(*channel as i32, 1)?;
+        }
+        for pin in &self.pins {
+            b.enable_reporting(*pin)?;
+        }
+
+        Ok(b)
+    }
+}
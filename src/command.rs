@@ -0,0 +1,167 @@
+//! This module contains an encoder for outbound firmata commands.
+use {ANALOG_MAPPING_QUERY, ANALOG_MESSAGE, CAPABILITY_QUERY, DIGITAL_MESSAGE, END_SYSEX,
+     EXTENDED_ANALOG, I2C_CONFIG, I2C_REQUEST, PIN_MODE,
+     PIN_STATE_QUERY, PROTOCOL_VERSION, REPORT_ANALOG, REPORT_DIGITAL, REPORT_FIRMWARE,
+     SAMPLING_INTERVAL, SERVO_CONFIG, SET_DIGITAL_PIN_VALUE, START_SYSEX, STEPPER_CONFIG,
+     STEPPER_DATA, STEPPER_STEP, STRING_DATA, SYSTEM_RESET};
+use {I2CRequest, PinMode, StepperInterface, StepperMove};
+
+/// A command that can be sent to a firmata device.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// Writes a 14-bit `value` to the analog or PWM `pin` (0-15).
+    AnalogMessage { pin: u8, value: u16 },
+    /// Writes the output `value` of the eight pins of a digital `port`.
+    DigitalMessage { port: u8, value: u8 },
+    /// Enables or disables reporting of an analog `channel`.
+    ReportAnalog { channel: u8, enabled: bool },
+    /// Enables or disables reporting of a digital `port`.
+    ReportDigital { port: u8, enabled: bool },
+    /// Sets the `mode` of a `pin`.
+    SetPinMode { pin: u8, mode: PinMode },
+    /// Sets the output `value` of a single digital `pin`. Needs protocol 2.5
+    /// or later.
+    SetDigitalPinValue { pin: u8, value: bool },
+    /// Asks the device for its protocol version.
+    ReportVersion,
+    /// Resets the device to its power-on state.
+    SystemReset,
+    /// Asks the device for the analog channel of each pin.
+    AnalogMappingQuery,
+    /// Asks the device for the modes supported by each pin.
+    CapabilityQuery,
+    /// Asks the device for the current mode and state of a `pin`.
+    PinStateQuery { pin: u8 },
+    /// Asks the device for its firmware name and version.
+    ReportFirmware,
+    /// Writes a `value` of any width to any `pin`.
+    ExtendedAnalog { pin: u8, value: u32 },
+    /// Sets the minimum and maximum pulse width in microseconds of a servo
+    /// `pin`.
+    ServoConfig { pin: u8, min_pulse: u16, max_pulse: u16 },
+    /// Sets how often, in milliseconds, the device reports analog values and
+    /// continuous I2C reads.
+    SamplingInterval { interval: u16 },
+    /// Sends a text message to the device.
+    StringData(String),
+    /// Sets the `delay` in microseconds between writing an I2C register and
+    /// reading it back.
+    I2CConfig { delay: u16 },
+    /// Reads from or writes to an I2C device.
+    I2CRequest(I2CRequest),
+    /// Configures the legacy stepper `device` (0-5) to drive a motor with
+    /// `steps_per_revolution` steps through the pins of `interface`.
+    StepperConfig { device: u8, interface: StepperInterface, steps_per_revolution: u16 },
+    /// Starts a move of a legacy stepper device.
+    StepperStep(StepperMove),
+    /// Sends any sysex command, such as ONEWIRE_DATA or SCHEDULER_DATA, with
+    /// `data` as its 7-bit payload.
+    Sysex { id: u8, data: Vec<u8> },
+}
+
+impl Command {
+    /// Appends the wire representation of the command to `buf`.
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        match *self {
+            Command::AnalogMessage { pin, value } => {
+                buf.push(ANALOG_MESSAGE | (pin & 0x0F));
+                push_u14(buf, value);
+            }
+            Command::DigitalMessage { port, value } => {
+                buf.push(DIGITAL_MESSAGE | (port & 0x0F));
+                push_u14(buf, value as u16);
+            }
+            Command::ReportAnalog { channel, enabled } => {
+                buf.extend_from_slice(&[REPORT_ANALOG | (channel & 0x0F), enabled as u8]);
+            }
+            Command::ReportDigital { port, enabled } => {
+                buf.extend_from_slice(&[REPORT_DIGITAL | (port & 0x0F), enabled as u8]);
+            }
+            Command::SetPinMode { pin, mode } => {
+                buf.extend_from_slice(&[PIN_MODE, pin & 0x7F, u8::from(mode) & 0x7F]);
+            }
+            Command::SetDigitalPinValue { pin, value } => {
+                buf.extend_from_slice(&[SET_DIGITAL_PIN_VALUE, pin & 0x7F, value as u8]);
+            }
+            Command::ReportVersion => buf.push(PROTOCOL_VERSION),
+            Command::SystemReset => buf.push(SYSTEM_RESET),
+            Command::AnalogMappingQuery => sysex(buf, ANALOG_MAPPING_QUERY, |_| {}),
+            Command::CapabilityQuery => sysex(buf, CAPABILITY_QUERY, |_| {}),
+            Command::PinStateQuery { pin } => {
+                sysex(buf, PIN_STATE_QUERY, |buf| buf.push(pin & 0x7F))
+            }
+            Command::ReportFirmware => sysex(buf, REPORT_FIRMWARE, |_| {}),
+            Command::ExtendedAnalog { pin, value } => {
+                sysex(buf, EXTENDED_ANALOG, |buf| {
+                    buf.push(pin & 0x7F);
+                    let mut value = value;
+                    loop {
+                        buf.push((value & 0x7F) as u8);
+                        value >>= 7;
+                        if value == 0 {
+                            break;
+                        }
+                    }
+                })
+            }
+            Command::ServoConfig { pin, min_pulse, max_pulse } => {
+                sysex(buf, SERVO_CONFIG, |buf| {
+                    buf.push(pin & 0x7F);
+                    push_u14(buf, min_pulse);
+                    push_u14(buf, max_pulse);
+                })
+            }
+            Command::SamplingInterval { interval } => {
+                sysex(buf, SAMPLING_INTERVAL, |buf| push_u14(buf, interval))
+            }
+            Command::StringData(ref text) => {
+                sysex(buf, STRING_DATA, |buf| {
+                    for b in text.bytes() {
+                        push_u14(buf, b as u16);
+                    }
+                })
+            }
+            Command::I2CConfig { delay } => sysex(buf, I2C_CONFIG, |buf| push_u14(buf, delay)),
+            Command::I2CRequest(ref request) => {
+                sysex(buf, I2C_REQUEST, |buf| request.encode(buf))
+            }
+            Command::StepperConfig { device, interface, steps_per_revolution } => {
+                sysex(buf, STEPPER_DATA, |buf| {
+                    buf.extend_from_slice(&[STEPPER_CONFIG, device & 0x7F, interface.bits()]);
+                    push_u14(buf, steps_per_revolution);
+                    buf.extend(interface.pins().iter().map(|pin| pin & 0x7F));
+                })
+            }
+            Command::StepperStep(ref step) => {
+                sysex(buf, STEPPER_DATA, |buf| {
+                    buf.push(STEPPER_STEP);
+                    step.encode(buf);
+                })
+            }
+            Command::Sysex { id, ref data } => {
+                sysex(buf, id & 0x7F, |buf| buf.extend(data.iter().map(|b| b & 0x7F)))
+            }
+        }
+    }
+
+    /// Returns the wire representation of the command.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = vec![];
+        self.encode(&mut buf);
+        buf
+    }
+}
+
+/// Wraps the bytes written by `body` in a sysex message with command `id`.
+pub(crate) fn sysex<F: FnOnce(&mut Vec<u8>)>(buf: &mut Vec<u8>, id: u8, body: F) {
+    buf.push(START_SYSEX);
+    buf.push(id);
+    body(buf);
+    buf.push(END_SYSEX);
+}
+
+/// Appends a 14-bit `value` as a 7-bit LSB/MSB pair.
+pub(crate) fn push_u14(buf: &mut Vec<u8>, value: u16) {
+    buf.push((value & 0x7F) as u8);
+    buf.push(((value >> 7) & 0x7F) as u8);
+}
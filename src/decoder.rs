@@ -0,0 +1,311 @@
+//! This module contains an incremental decoder for inbound firmata messages.
+use command::{push_u14, sysex};
+use {Error, I2CReply, Mode, PinMode, Result};
+use {ANALOG_MAPPING_RESPONSE, ANALOG_MESSAGE, CAPABILITY_RESPONSE, DIGITAL_MESSAGE, END_SYSEX,
+     I2C_REPLY, PIN_STATE_RESPONSE, PROTOCOL_VERSION, REPORT_FIRMWARE, START_SYSEX, STEPPER_DATA,
+     STRING_DATA};
+
+/// A message received from a firmata device.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FirmataMessage {
+    /// The firmata protocol version implemented by the device.
+    ProtocolVersion { major: u8, minor: u8 },
+    /// The value of an analog `channel`.
+    AnalogMessage { channel: u8, value: i32 },
+    /// The values of the eight pins of a digital `port`.
+    DigitalMessage { port: u8, value: i32 },
+    /// The modes supported by each pin, indexed by pin number.
+    CapabilityResponse { pins: Vec<Vec<Mode>> },
+    /// The analog channel of each pin, indexed by pin number. Pins without
+    /// analog input are `None`.
+    AnalogMappingResponse { channels: Vec<Option<u8>> },
+    /// The current `mode` and `state` of a `pin`.
+    PinStateResponse { pin: u8, mode: PinMode, state: i32 },
+    /// The firmware name and version running on the device.
+    ReportFirmware { major: u8, minor: u8, name: String },
+    /// Data read from an I2C device.
+    I2CReply(I2CReply),
+    /// A text message sent by the firmware.
+    StringData(String),
+    /// The legacy stepper `device` finished its move.
+    StepperMoveComplete { device: u8 },
+    /// A sysex message with a command `id` this crate does not decode.
+    UnknownSysex { id: u8, data: Vec<u8> },
+}
+
+impl FirmataMessage {
+    /// Appends the wire representation of the message, as a device would
+    /// send it, to `buf`.
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        match *self {
+            FirmataMessage::ProtocolVersion { major, minor } => {
+                buf.extend_from_slice(&[PROTOCOL_VERSION, major & 0x7F, minor & 0x7F]);
+            }
+            FirmataMessage::AnalogMessage { channel, value } => {
+                buf.push(ANALOG_MESSAGE | (channel & 0x0F));
+                push_u14(buf, value as u16);
+            }
+            FirmataMessage::DigitalMessage { port, value } => {
+                buf.push(DIGITAL_MESSAGE | (port & 0x0F));
+                push_u14(buf, value as u16);
+            }
+            FirmataMessage::CapabilityResponse { ref pins } => {
+                sysex(buf, CAPABILITY_RESPONSE, |buf| {
+                    for modes in pins {
+                        for mode in modes {
+                            buf.extend_from_slice(&[u8::from(mode.mode), mode.resolution]);
+                        }
+                        buf.push(127u8);
+                    }
+                })
+            }
+            FirmataMessage::AnalogMappingResponse { ref channels } => {
+                sysex(buf, ANALOG_MAPPING_RESPONSE, |buf| {
+                    buf.extend(channels.iter().map(|c| c.unwrap_or(127u8)));
+                })
+            }
+            FirmataMessage::PinStateResponse { pin, mode, state } => {
+                sysex(buf, PIN_STATE_RESPONSE, |buf| {
+                    buf.extend_from_slice(&[pin & 0x7F, u8::from(mode)]);
+                    let mut state = state as u32;
+                    loop {
+                        buf.push((state & 0x7F) as u8);
+                        state >>= 7;
+                        if state == 0 {
+                            break;
+                        }
+                    }
+                })
+            }
+            FirmataMessage::ReportFirmware { major, minor, ref name } => {
+                sysex(buf, REPORT_FIRMWARE, |buf| {
+                    buf.extend_from_slice(&[major & 0x7F, minor & 0x7F]);
+                    encode_bytes(buf, name.as_bytes());
+                })
+            }
+            FirmataMessage::I2CReply(ref reply) => {
+                sysex(buf, I2C_REPLY, |buf| {
+                    push_u14(buf, reply.address as u16);
+                    push_u14(buf, reply.register as u16);
+                    encode_bytes(buf, &reply.data);
+                })
+            }
+            FirmataMessage::StringData(ref text) => {
+                sysex(buf, STRING_DATA, |buf| encode_bytes(buf, text.as_bytes()))
+            }
+            FirmataMessage::StepperMoveComplete { device } => {
+                sysex(buf, STEPPER_DATA, |buf| buf.push(device & 0x7F))
+            }
+            FirmataMessage::UnknownSysex { id, ref data } => {
+                sysex(buf, id, |buf| buf.extend_from_slice(data))
+            }
+        }
+    }
+
+    /// Returns the wire representation of the message.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = vec![];
+        self.encode(&mut buf);
+        buf
+    }
+}
+
+/// A streaming decoder that turns raw bytes into `FirmataMessage`s.
+///
+/// Bytes may be pushed in arbitrarily sized chunks, partial frames are kept
+/// until the rest of the message arrives and bytes that do not belong to a
+/// message are skipped until the next command byte.
+#[derive(Debug, Default)]
+pub struct Decoder {
+    buf: Vec<u8>,
+}
+
+impl Decoder {
+    /// Creates a new `Decoder` with an empty buffer.
+    pub fn new() -> Decoder {
+        Decoder { buf: vec![] }
+    }
+
+    /// Appends `data` to the bytes waiting to be decoded.
+    pub fn push(&mut self, data: &[u8]) {
+        self.buf.extend_from_slice(data);
+    }
+
+    /// Returns the number of buffered bytes that have not been decoded yet.
+    pub fn pending(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Decodes the next complete message from the buffer.
+    ///
+    /// Returns `Ok(None)` when more bytes are needed. A frame interrupted by
+    /// another command byte is discarded and reported as `Error::Framing`;
+    /// decoding resumes at the interrupting byte on the next call.
+    pub fn decode(&mut self) -> Result<Option<FirmataMessage>> {
+        let start = self
+            .buf
+            .iter()
+            .position(|b| is_command(*b))
+            .unwrap_or(self.buf.len());
+        self.buf.drain(..start);
+
+        if self.buf.is_empty() {
+            return Ok(None);
+        }
+
+        let command = self.buf[0];
+        let (len, body) = if command == START_SYSEX {
+            match self.buf.iter().position(|b| *b == END_SYSEX) {
+                Some(end) => (end + 1, end),
+                None => (self.buf.len() + 1, self.buf.len()),
+            }
+        } else {
+            (3, self.buf.len().min(3))
+        };
+
+        // Data bytes never have their high bit set, so any byte that does
+        // inside the frame means the frame was cut short.
+        if let Some(i) = self.buf[1..body].iter().position(|b| b & 0x80 != 0) {
+            self.buf.drain(..i + 1);
+            return Err(Error::Framing("truncated message".to_string()));
+        }
+
+        if self.buf.len() < len {
+            return Ok(None);
+        }
+
+        let frame: Vec<u8> = self.buf.drain(..len).collect();
+        let message = match command {
+            PROTOCOL_VERSION => FirmataMessage::ProtocolVersion {
+                major: frame[1],
+                minor: frame[2],
+            },
+            ANALOG_MESSAGE..=0xEF => FirmataMessage::AnalogMessage {
+                channel: command & 0x0F,
+                value: (frame[1] as i32) | ((frame[2] as i32) << 7),
+            },
+            DIGITAL_MESSAGE..=0x9F => FirmataMessage::DigitalMessage {
+                port: command & 0x0F,
+                value: (frame[1] as i32) | ((frame[2] as i32) << 7),
+            },
+            _ => {
+                if frame.len() < 3 {
+                    return Err(Error::Framing("empty sysex message".to_string()));
+                }
+                decode_sysex(frame[1], &frame[2..frame.len() - 1])?
+            }
+        };
+
+        Ok(Some(message))
+    }
+}
+
+fn decode_sysex(id: u8, data: &[u8]) -> Result<FirmataMessage> {
+    let message = match id {
+        CAPABILITY_RESPONSE => {
+            let mut pins = vec![];
+            let mut modes = vec![];
+            let mut i = 0;
+
+            while i < data.len() {
+                if data[i] == 127u8 {
+                    pins.push(modes);
+                    modes = vec![];
+                    i += 1;
+                    continue;
+                }
+                if i + 1 >= data.len() {
+                    return Err(short("capability response"));
+                }
+                modes.push(Mode {
+                    mode: PinMode::from(data[i]),
+                    resolution: data[i + 1],
+                });
+                i += 2;
+            }
+
+            FirmataMessage::CapabilityResponse { pins }
+        }
+        ANALOG_MAPPING_RESPONSE => FirmataMessage::AnalogMappingResponse {
+            channels: data
+                .iter()
+                .map(|c| if *c == 127u8 { None } else { Some(*c) })
+                .collect(),
+        },
+        PIN_STATE_RESPONSE => {
+            if data.len() < 3 {
+                return Err(short("pin state response"));
+            }
+            // The state is sent as up to four 7-bit bytes, LSB first.
+            let state = data[2..]
+                .iter()
+                .take(4)
+                .enumerate()
+                .fold(0i32, |state, (i, b)| state | ((*b as i32) << (7 * i)));
+
+            FirmataMessage::PinStateResponse {
+                pin: data[0],
+                mode: PinMode::from(data[1]),
+                state,
+            }
+        }
+        REPORT_FIRMWARE => {
+            if data.len() < 2 {
+                return Err(short("firmware report"));
+            }
+            FirmataMessage::ReportFirmware {
+                major: data[0],
+                minor: data[1],
+                name: decode_string(&data[2..])?,
+            }
+        }
+        I2C_REPLY => {
+            if data.len() < 4 {
+                return Err(short("i2c reply"));
+            }
+            FirmataMessage::I2CReply(I2CReply {
+                address: (data[0] as i32) | ((data[1] as i32) << 7),
+                register: (data[2] as i32) | ((data[3] as i32) << 7),
+                data: decode_bytes(&data[4..]),
+            })
+        }
+        STRING_DATA => FirmataMessage::StringData(decode_string(data)?),
+        STEPPER_DATA if !data.is_empty() => FirmataMessage::StepperMoveComplete { device: data[0] },
+        _ => FirmataMessage::UnknownSysex {
+            id,
+            data: data.to_vec(),
+        },
+    };
+
+    Ok(message)
+}
+
+/// Encodes bytes as 7-bit LSB/MSB pairs.
+fn encode_bytes(buf: &mut Vec<u8>, data: &[u8]) {
+    for b in data {
+        push_u14(buf, *b as u16);
+    }
+}
+
+/// Decodes bytes sent as 7-bit LSB/MSB pairs.
+pub(crate) fn decode_bytes(data: &[u8]) -> Vec<u8> {
+    data.chunks(2)
+        .filter(|pair| pair.len() == 2)
+        .map(|pair| ((pair[0] as u16) | ((pair[1] as u16) << 7)) as u8)
+        .collect()
+}
+
+/// Decodes a string sent as 7-bit LSB/MSB pairs.
+fn decode_string(data: &[u8]) -> Result<String> {
+    Ok(String::from_utf8(decode_bytes(data))?)
+}
+
+fn short(message: &str) -> Error {
+    Error::Framing(format!("short {}", message))
+}
+
+fn is_command(byte: u8) -> bool {
+    matches!(byte, ANALOG_MESSAGE..=0xEF | DIGITAL_MESSAGE..=0x9F)
+        || byte == PROTOCOL_VERSION
+        || byte == START_SYSEX
+}
@@ -0,0 +1,78 @@
+//! This module contains the error type used throughout this crate.
+use std::error;
+use std::fmt;
+use std::io;
+use std::result;
+use std::string::FromUtf8Error;
+
+use PinMode;
+
+/// A specialized `Result` type for firmata operations.
+pub type Result<T> = result::Result<T, Error>;
+
+/// An error raised while communicating with a firmata device.
+#[derive(Debug)]
+pub enum Error {
+    /// Reading from or writing to the connection failed.
+    Io(io::Error),
+    /// The device did not answer in time.
+    Timeout,
+    /// A message was cut short or is too short to decode.
+    Framing(String),
+    /// The device sent a sysex message with a command `id` this crate does
+    /// not decode.
+    UnknownSysex { id: u8, payload: Vec<u8> },
+    /// The pin does not exist on the device.
+    InvalidPin(i32),
+    /// The pin does not support the requested mode.
+    UnsupportedMode { pin: i32, mode: PinMode },
+    /// The board did not report the protocol version a command needs.
+    UnsupportedProtocol { required: (u8, u8), reported: (u8, u8) },
+    /// An argument does not fit in the range the protocol can encode.
+    OutOfRange(&'static str),
+    /// A string sent by the device is not valid UTF-8.
+    Utf8(FromUtf8Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Io(ref e) => write!(f, "i/o error: {}", e),
+            Error::Timeout => write!(f, "timed out waiting for the device"),
+            Error::Framing(ref reason) => write!(f, "framing error: {}", reason),
+            Error::UnknownSysex { id, .. } => write!(f, "unknown sysex code {:#04x}", id),
+            Error::InvalidPin(pin) => write!(f, "invalid pin {}", pin),
+            Error::UnsupportedMode { pin, mode } => {
+                write!(f, "pin {} does not support mode {:?}", pin, mode)
+            }
+            Error::UnsupportedProtocol { required, reported } => {
+                write!(f, "protocol {}.{} required, board reported {}.{}",
+                       required.0, required.1, reported.0, reported.1)
+            }
+            Error::OutOfRange(what) => write!(f, "{} is out of range", what),
+            Error::Utf8(ref e) => write!(f, "malformed utf-8: {}", e),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::Io(ref e) => Some(e),
+            Error::Utf8(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Io(e)
+    }
+}
+
+impl From<FromUtf8Error> for Error {
+    fn from(e: FromUtf8Error) -> Error {
+        Error::Utf8(e)
+    }
+}
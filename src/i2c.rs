@@ -0,0 +1,147 @@
+//! This module contains the typed options of an I2C request.
+use {Error, Result};
+use {I2C_10BIT_ADDRESS, I2C_AUTO_RESTART, I2C_MODE_READ, I2C_MODE_READ_CONTINUOUSLY,
+     I2C_MODE_STOP_READING, I2C_MODE_WRITE};
+
+/// The operation performed by an I2C request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum I2CMode {
+    /// Writes data to the device.
+    Write,
+    /// Reads from the device once.
+    Read,
+    /// Reads from the device every sampling interval until stopped.
+    ReadContinuously,
+    /// Stops a continuous read of the device.
+    StopReading,
+}
+
+impl I2CMode {
+    fn bits(self) -> u8 {
+        match self {
+            I2CMode::Write => I2C_MODE_WRITE,
+            I2CMode::Read => I2C_MODE_READ,
+            I2CMode::ReadContinuously => I2C_MODE_READ_CONTINUOUSLY,
+            I2CMode::StopReading => I2C_MODE_STOP_READING,
+        }
+    }
+}
+
+/// An I2C request addressed to a single device.
+#[derive(Debug, Clone, PartialEq)]
+pub struct I2CRequest {
+    /// The 7-bit, or with `ten_bit_address` 10-bit, device address.
+    pub address: u16,
+    pub mode: I2CMode,
+    /// Whether `address` is a 10-bit address.
+    pub ten_bit_address: bool,
+    /// Whether to send a restart instead of a stop between writing the
+    /// register and reading the data.
+    pub auto_restart: bool,
+    /// The register to read from or write to, if any.
+    pub register: Option<u16>,
+    /// The number of bytes to read.
+    pub size: u16,
+    /// The bytes to write.
+    pub data: Vec<u8>,
+}
+
+impl I2CRequest {
+    fn new(address: u16, mode: I2CMode) -> I2CRequest {
+        I2CRequest {
+            address,
+            mode,
+            ten_bit_address: false,
+            auto_restart: false,
+            register: None,
+            size: 0,
+            data: vec![],
+        }
+    }
+
+    /// Creates a request writing `data` to the device at `address`.
+    pub fn write(address: u16, data: &[u8]) -> I2CRequest {
+        I2CRequest { data: data.to_vec(), ..I2CRequest::new(address, I2CMode::Write) }
+    }
+
+    /// Creates a request reading `size` bytes once from the device at
+    /// `address`.
+    pub fn read(address: u16, size: u16) -> I2CRequest {
+        I2CRequest { size, ..I2CRequest::new(address, I2CMode::Read) }
+    }
+
+    /// Creates a request reading `size` bytes every sampling interval from
+    /// the device at `address` until `stop_reading` is sent.
+    pub fn read_continuously(address: u16, size: u16) -> I2CRequest {
+        I2CRequest { size, ..I2CRequest::new(address, I2CMode::ReadContinuously) }
+    }
+
+    /// Creates a request stopping all continuous reads of the device at
+    /// `address`.
+    pub fn stop_reading(address: u16) -> I2CRequest {
+        I2CRequest::new(address, I2CMode::StopReading)
+    }
+
+    /// Reads from or writes to `register` instead of the device's current
+    /// register.
+    pub fn register(mut self, register: u16) -> I2CRequest {
+        self.register = Some(register);
+        self
+    }
+
+    /// Treats the address as a 10-bit address.
+    pub fn ten_bit_address(mut self) -> I2CRequest {
+        self.ten_bit_address = true;
+        self
+    }
+
+    /// Sends a restart instead of a stop between writing the register and
+    /// reading the data.
+    pub fn auto_restart(mut self) -> I2CRequest {
+        self.auto_restart = true;
+        self
+    }
+
+    /// Checks that the address and register fit in their wire encoding.
+    pub fn validate(&self) -> Result<()> {
+        let max_address = if self.ten_bit_address { 0x3FF } else { 0x7F };
+        if self.address > max_address {
+            return Err(Error::OutOfRange("i2c address"));
+        }
+        if self.register.is_some_and(|r| r > 0x3FFF) || self.size > 0x3FFF {
+            return Err(Error::OutOfRange("i2c register or size"));
+        }
+        // A write sends the register as the first data byte.
+        if self.mode == I2CMode::Write && self.register.is_some_and(|r| r > 0xFF) {
+            return Err(Error::OutOfRange("i2c register"));
+        }
+        Ok(())
+    }
+
+    /// Appends the body of the I2C_REQUEST sysex message to `buf`.
+    pub(crate) fn encode(&self, buf: &mut Vec<u8>) {
+        let mut flags = self.mode.bits() << 3 | ((self.address >> 7) & 0x07) as u8;
+        if self.ten_bit_address {
+            flags |= I2C_10BIT_ADDRESS;
+        }
+        if self.auto_restart {
+            flags |= I2C_AUTO_RESTART;
+        }
+        buf.extend_from_slice(&[(self.address & 0x7F) as u8, flags]);
+
+        match self.mode {
+            I2CMode::Write => {
+                let register = self.register.map(|r| r as u8);
+                for b in register.iter().chain(self.data.iter()) {
+                    buf.extend_from_slice(&[b & 0x7F, b >> 7]);
+                }
+            }
+            I2CMode::Read | I2CMode::ReadContinuously => {
+                for value in self.register.iter().chain(Some(self.size).iter()) {
+                    buf.extend_from_slice(&[(value & 0x7F) as u8, ((value >> 7) & 0x7F) as u8]);
+                }
+            }
+            I2CMode::StopReading => {}
+        }
+    }
+}
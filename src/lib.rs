@@ -1,10 +1,44 @@
 //! This module contains a client implementation of the
 //! [Firmata Protocol](https://github.com/firmata/protocol)
-use std::str;
+use std::collections::{HashMap, VecDeque};
 use std::io;
+use std::io::ErrorKind;
 use std::thread;
-use std::time::Duration;
-use std::io::{Write, Read, Error, Result, ErrorKind};
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "serial")]
+extern crate serial;
+
+mod builder;
+mod command;
+mod decoder;
+mod error;
+mod i2c;
+mod mock;
+mod profile;
+mod reporting;
+mod servo;
+mod simulator;
+mod stepper;
+#[cfg(feature = "serial")]
+mod serial_port;
+mod tcp;
+
+pub use builder::BoardBuilder;
+pub use command::Command;
+pub use decoder::{Decoder, FirmataMessage};
+pub use error::{Error, Result};
+pub use i2c::{I2CMode, I2CRequest};
+pub use mock::MockConnection;
+pub use profile::Profile;
+pub use servo::Servo;
+pub use simulator::{I2CDevice, I2CMemory, SimulatedPin, Simulator};
+pub use stepper::{StepDirection, StepperInterface, StepperMove};
+#[cfg(feature = "serial")]
+pub use serial_port::{candidate_ports, detect_boards, open_port, DetectedBoard};
+pub use tcp::open_tcp_stream;
+
+use reporting::Reporting;
 
 pub const ENCODER_DATA: u8 = 0x61;
 pub const ANALOG_MAPPING_QUERY: u8 = 0x69;
@@ -17,6 +51,8 @@ pub const EXTENDED_ANALOG: u8 = 0x6F;
 pub const SERVO_CONFIG: u8 = 0x70;
 pub const STRING_DATA: u8 = 0x71;
 pub const STEPPER_DATA: u8 = 0x72;
+pub const STEPPER_CONFIG: u8 = 0x00;
+pub const STEPPER_STEP: u8 = 0x01;
 pub const ONEWIRE_DATA: u8 = 0x73;
 pub const SHIFT_DATA: u8 = 0x75;
 pub const I2C_REQUEST: u8 = 0x76;
@@ -24,68 +60,132 @@ pub const I2C_REPLY: u8 = 0x77;
 pub const I2C_CONFIG: u8 = 0x78;
 pub const I2C_MODE_WRITE: u8 = 0x00;
 pub const I2C_MODE_READ: u8 = 0x01;
+pub const I2C_MODE_READ_CONTINUOUSLY: u8 = 0x02;
+pub const I2C_MODE_STOP_READING: u8 = 0x03;
+pub const I2C_10BIT_ADDRESS: u8 = 0x20;
+pub const I2C_AUTO_RESTART: u8 = 0x40;
 pub const REPORT_FIRMWARE: u8 = 0x79;
 pub const PROTOCOL_VERSION: u8 = 0xF9;
-pub const SAMPLEING_INTERVAL: u8 = 0x7A;
+pub const SAMPLING_INTERVAL: u8 = 0x7A;
+#[deprecated(note = "use `SAMPLING_INTERVAL`")]
+pub const SAMPLEING_INTERVAL: u8 = SAMPLING_INTERVAL;
 pub const SCHEDULER_DATA: u8 = 0x7B;
 pub const SYSEX_NON_REALTIME: u8 = 0x7E;
 pub const SYSEX_REALTIME: u8 = 0x7F;
 pub const START_SYSEX: u8 = 0xF0;
 pub const END_SYSEX: u8 = 0xF7;
 pub const PIN_MODE: u8 = 0xF4;
+pub const SET_DIGITAL_PIN_VALUE: u8 = 0xF5;
 pub const REPORT_DIGITAL: u8 = 0xD0;
 pub const REPORT_ANALOG: u8 = 0xC0;
 pub const DIGITAL_MESSAGE: u8 = 0x90;
 pub const ANALOG_MESSAGE: u8 = 0xE0;
+pub const SYSTEM_RESET: u8 = 0xFF;
 
+#[deprecated(note = "use `PinMode::Input`")]
 pub const INPUT: u8 = 0;
+#[deprecated(note = "use `PinMode::Output`")]
 pub const OUTPUT: u8 = 1;
+#[deprecated(note = "use `PinMode::Analog`")]
 pub const ANALOG: u8 = 2;
+#[deprecated(note = "use `PinMode::Pwm`")]
 pub const PWM: u8 = 3;
+#[deprecated(note = "use `PinMode::Servo`")]
 pub const SERVO: u8 = 4;
+#[deprecated(note = "use `PinMode::I2C`")]
 pub const I2C: u8 = 6;
+#[deprecated(note = "use `PinMode::OneWire`")]
 pub const ONEWIRE: u8 = 7;
+#[deprecated(note = "use `PinMode::Stepper`")]
 pub const STEPPER: u8 = 8;
+#[deprecated(note = "use `PinMode::Encoder`")]
 pub const ENCODER: u8 = 9;
 
-fn read<T: io::Read>(port: &mut T, len: i32) -> Result<(Vec<u8>)> {
-    let mut vec: Vec<u8> = vec![];
-    let mut len = len;
+/// The number of stepper devices the legacy stepper firmware drives.
+pub const MAX_STEPPERS: u8 = 6;
 
-    loop {
-        let buf: &mut [u8; 1] = &mut [0u8];
+/// The sampling interval in milliseconds StandardFirmata starts with.
+pub const DEFAULT_SAMPLING_INTERVAL: u64 = 19;
 
-        match port.read(buf) {
-            Ok(_) => {
-                vec.push(buf[0]);
-                len = len - 1;
-                if len == 0 {
-                   break;
-                }
-            }
-            Err(e) => {
-                 if e.kind() == ErrorKind::TimedOut {
-                    thread::sleep(Duration::from_millis(1));
-                    continue
-                }
-            } }
-    }
+/// The time in milliseconds queries wait for the board to answer by default.
+/// It leaves room for boards that reset when the connection is opened.
+pub const DEFAULT_TIMEOUT: u64 = 5000;
 
-    return Ok(vec);
-}
 
 /// A structure representing an I2C reply.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct I2CReply {
     pub address: i32,
     pub register: i32,
     pub data: Vec<u8>,
 }
 
+/// An enumeration of the pin modes defined by StandardFirmata.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PinMode {
+    Input,
+    Output,
+    Analog,
+    Pwm,
+    Servo,
+    Shift,
+    I2C,
+    OneWire,
+    Stepper,
+    Encoder,
+    Serial,
+    Pullup,
+    Ignore,
+    /// A mode byte this crate does not know about.
+    Unknown(u8),
+}
+
+impl From<u8> for PinMode {
+    fn from(mode: u8) -> PinMode {
+        match mode {
+            0x00 => PinMode::Input,
+            0x01 => PinMode::Output,
+            0x02 => PinMode::Analog,
+            0x03 => PinMode::Pwm,
+            0x04 => PinMode::Servo,
+            0x05 => PinMode::Shift,
+            0x06 => PinMode::I2C,
+            0x07 => PinMode::OneWire,
+            0x08 => PinMode::Stepper,
+            0x09 => PinMode::Encoder,
+            0x0A => PinMode::Serial,
+            0x0B => PinMode::Pullup,
+            0x7F => PinMode::Ignore,
+            mode => PinMode::Unknown(mode),
+        }
+    }
+}
+
+impl From<PinMode> for u8 {
+    fn from(mode: PinMode) -> u8 {
+        match mode {
+            PinMode::Input => 0x00,
+            PinMode::Output => 0x01,
+            PinMode::Analog => 0x02,
+            PinMode::Pwm => 0x03,
+            PinMode::Servo => 0x04,
+            PinMode::Shift => 0x05,
+            PinMode::I2C => 0x06,
+            PinMode::OneWire => 0x07,
+            PinMode::Stepper => 0x08,
+            PinMode::Encoder => 0x09,
+            PinMode::Serial => 0x0A,
+            PinMode::Pullup => 0x0B,
+            PinMode::Ignore => 0x7F,
+            PinMode::Unknown(mode) => mode,
+        }
+    }
+}
+
 /// A structure representing an available pin mode.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Mode {
-    pub mode: u8,
+    pub mode: PinMode,
     pub resolution: u8
 }
 
@@ -93,16 +193,74 @@ pub struct Mode {
 #[derive(Debug)]
 pub struct Pin {
     pub modes: Vec<Mode>,
-    pub analog: bool,
-    pub value: i32,
-    pub mode: u8,
+    /// The analog input channel of the pin, if it has one.
+    pub analog_channel: Option<u8>,
+    pub mode: PinMode,
+    /// The resolution in bits of the current mode, or 0 if the pin did not
+    /// advertise it.
+    pub resolution: u8,
+    /// The last value written to the pin.
+    pub commanded_value: i32,
+    /// When `commanded_value` was written, if ever.
+    pub commanded_at: Option<Instant>,
+    /// The last value the board reported for the pin.
+    pub reported_value: i32,
+    /// When `reported_value` arrived, if ever.
+    pub reported_at: Option<Instant>,
+    /// Whether `mode` was set or reported since the capabilities arrived.
+    mode_known: bool,
+}
+
+impl Pin {
+    fn new(modes: Vec<Mode>, analog_channel: Option<u8>) -> Pin {
+        let mut pin = Pin {
+            modes,
+            analog_channel,
+            mode: PinMode::Input,
+            resolution: 0,
+            commanded_value: 0,
+            commanded_at: None,
+            reported_value: 0,
+            reported_at: None,
+            mode_known: false,
+        };
+        pin.set_mode(PinMode::Input);
+        pin.mode_known = false;
+        pin
+    }
+
+    fn set_mode(&mut self, mode: PinMode) {
+        self.mode = mode;
+        self.mode_known = true;
+        self.resolution = self
+            .modes
+            .iter()
+            .find(|m| m.mode == mode)
+            .map_or(0, |m| m.resolution);
+    }
+
+    fn command(&mut self, value: i32) {
+        self.commanded_value = value;
+        self.commanded_at = Some(Instant::now());
+    }
+
+    fn report(&mut self, value: i32) {
+        self.reported_value = value;
+        self.reported_at = Some(Instant::now());
+    }
 }
 
 /// A trait for implementing firmata boards.
 pub trait Firmata {
-    /// This function returns the raw I2C replies that have been read from
-    /// the board.
-    fn i2c_data(&mut self) -> &mut Vec<I2CReply>;
+    /// This function returns the I2C replies that have been read from the
+    /// board, queued in arrival order per (address, register).
+    fn i2c_data(&mut self) -> &mut HashMap<(i32, i32), VecDeque<I2CReply>>;
+    /// This function returns the strings sent by the firmware that have been
+    /// read from the board.
+    fn string_data(&mut self) -> &mut Vec<String>;
+    /// This function returns the stepper devices whose move completed and
+    /// has not been waited for, in arrival order.
+    fn completed_moves(&mut self) -> &mut Vec<u8>;
     /// This function returns the pins that the board has access to.
     fn pins(&mut self) -> &Vec<Pin>;
     /// This function returns the current firmata protocol version.
@@ -111,16 +269,50 @@ pub trait Firmata {
     fn firmware_name(&mut self) -> &String;
     /// This function returns the firmware version.
     fn firmware_version(&mut self) -> &String;
-    /// This function queries the board for available analog pins.
+    /// This function returns the interval at which the board reports analog
+    /// values and continuous I2C reads.
+    fn sampling_interval(&mut self) -> Duration;
+    /// This function returns how long the query functions wait for the
+    /// board to answer.
+    fn timeout(&mut self) -> Duration;
+    /// This function sets how long the query functions wait for the board
+    /// to answer.
+    fn set_timeout(&mut self, timeout: Duration);
+    /// This function queries the board for available analog pins and waits
+    /// for the answer. Messages that arrive in the meantime are applied as
+    /// usual.
     fn query_analog_mapping(&mut self) -> Result<()>;
-    /// This function queries the board for all available capabilities.
+    /// This function returns the analog channel of `pin`, or `None` if the
+    /// pin has no analog input or the analog mapping has not been queried.
+    fn analog_channel(&mut self, pin: i32) -> Option<u8>;
+    /// This function returns the pin of the analog `channel`, or `None` if
+    /// no pin was mapped to it.
+    fn analog_pin(&mut self, channel: u8) -> Option<i32>;
+    /// This function queries the board for all available capabilities and
+    /// waits for the answer. Messages that arrive in the meantime are
+    /// applied as usual.
     fn query_capabilities(&mut self) -> Result<()>;
     /// This function queries the board for current firmware and protocol
-    /// information.
+    /// information and waits for both answers. Messages that arrive in the
+    /// meantime are applied as usual.
     fn query_firmware(&mut self) -> Result<()>;
+    /// This function queries the board for the current mode and value of
+    /// `pin` and waits for the answer. Messages that arrive in the meantime
+    /// are applied as usual.
+    fn query_pin_state(&mut self, pin: i32) -> Result<()>;
+    /// This function queries the state of every pin and waits until the
+    /// board has answered for all of them.
+    fn refresh_pin_states(&mut self) -> Result<()>;
+    /// This function sets how often the board reports analog values and
+    /// continuous I2C reads. The interval is sent in whole milliseconds and
+    /// must not exceed 16383ms; shorter than 1ms is rounded up to the 1ms
+    /// minimum of the firmware.
+    fn set_sampling_interval(&mut self, interval: Duration) -> Result<()>;
+    /// This function sends `text` to the firmware as a string message.
+    fn send_string(&mut self, text: &str) -> Result<()>;
     /// This function configures the `delay` in microseconds for I2C devices
     /// that require a delay between when the register is written to and the
-    /// data in that register can be read.
+    /// data in that register can be read. The delay must fit in 14 bits.
     fn i2c_config(&mut self, delay: i32) -> Result<()>;
     /// This function reads `size` bytes from I2C device at the specified
     /// `address`.
@@ -128,56 +320,299 @@ pub trait Firmata {
     /// This function writes `data` to the I2C device at
     /// the specified `address`.
     fn i2c_write(&mut self, address: i32, data: &[u8]) -> Result<()>;
-    /// This function sets the digital reporting `state`
-    /// of the specified `pin`.
-    fn report_digital(&mut self, pin: i32, state: i32) -> Result<()>;
-    /// This function sets the analog reporting `state` of the specified `pin`.
-    fn report_analog(&mut self, pin: i32, state: i32) -> Result<()>;
-    /// This function writes `level` to the analog `pin`.
+    /// This function reads `size` bytes from `register`, or the current
+    /// register if `None`, of the I2C device at `address` and waits up to
+    /// `timeout` for the reply. Replies queued before the request was sent
+    /// are left in `i2c_data`; the returned reply is taken out of it.
+    fn i2c_read_reply(&mut self, address: i32, register: Option<i32>, size: i32,
+                      timeout: Duration) -> Result<I2CReply>;
+    /// This function sends an I2C `request`, which can read from a register,
+    /// start or stop continuous reads and use 10-bit addressing.
+    fn i2c_request(&mut self, request: &I2CRequest) -> Result<()>;
+    /// This function sets the digital reporting `state` of the digital
+    /// `port`, which holds pins `8 * port` to `8 * port + 7`.
+    fn report_digital(&mut self, port: i32, state: i32) -> Result<()>;
+    /// This function sets the analog reporting `state` of the analog
+    /// `channel`. `analog_channel` returns the channel of a pin.
+    fn report_analog(&mut self, channel: i32, state: i32) -> Result<()>;
+    /// This function enables reports of `pin`: of its analog channel if the
+    /// pin is in analog mode, of its digital port otherwise. A pin whose mode
+    /// was never set or queried counts as analog if it has a channel, as the
+    /// firmware starts such pins in analog mode. A port shared
+    /// by several pins stays reported until all of them disabled reporting.
+    /// It fails with `Error::UnsupportedMode` if the pin can't be reported.
+    fn enable_reporting(&mut self, pin: i32) -> Result<()>;
+    /// This function disables reports of `pin`. Its port or channel is only
+    /// disabled once no other pin wants reports of it.
+    fn disable_reporting(&mut self, pin: i32) -> Result<()>;
+    /// This function returns the digital ports being reported.
+    fn reporting_ports(&mut self) -> Vec<u8>;
+    /// This function returns the analog channels being reported.
+    fn reporting_channels(&mut self) -> Vec<u8>;
+    /// This function writes `level` to the analog `pin`, using the extended
+    /// analog sysex when the pin is above 15 or the level does not fit in 14
+    /// bits. It fails with `Error::InvalidPin` if the board has no such pin
+    /// and with `Error::OutOfRange` for negative levels or pins above 127.
     fn analog_write(&mut self, pin: i32, level: i32) -> Result<()>;
-    /// This function writes `level` to the digital `pin`.
+    /// This function writes `level` to the digital `pin`. Boards speaking
+    /// protocol 2.5 or later get a single pin update, older ones the whole
+    /// port composed from the cached pin values. It fails with
+    /// `Error::InvalidPin` if the board has no such pin.
     fn digital_write(&mut self, pin: i32, level: i32) -> Result<()>;
-    /// This function sets the `mode` of the specified `pin`.
-    fn set_pin_mode(&mut self, pin: i32, mode: u8) -> Result<()>;
-    /// This function reads from the firmata device and parses one firmata
-    /// message.
-    fn read_and_decode(&mut self) -> Result<()>;
+    /// This function writes `level` to the digital `pin` alone, without
+    /// touching the other pins of its port. It fails with
+    /// `Error::UnsupportedProtocol` unless the board reported protocol 2.5 or
+    /// later and with `Error::InvalidPin` if the board has no such pin.
+    fn set_digital_pin_value(&mut self, pin: i32, level: i32) -> Result<()>;
+    /// This function sets the `mode` of the specified `pin`. The firmware
+    /// reports an analog pin's channel only while the pin is in analog mode,
+    /// and `reporting_channels` follows that. It fails with
+    /// `Error::InvalidPin` if the board has no such pin and with
+    /// `Error::UnsupportedMode` if the pin does not advertise `mode`.
+    fn set_pin_mode(&mut self, pin: i32, mode: PinMode) -> Result<()>;
+    /// This function attaches a servo to `pin` with the given pulse width
+    /// range in microseconds and puts the pin in `PinMode::Servo`.
+    fn servo_config(&mut self, pin: i32, min_pulse: i32, max_pulse: i32) -> Result<()>;
+    /// This function configures the legacy stepper `device` (0-5) to drive
+    /// a motor with `steps_per_revolution` steps through the pins of
+    /// `interface` and puts those pins in `PinMode::Stepper`.
+    fn stepper_config(&mut self, device: i32, interface: StepperInterface,
+                      steps_per_revolution: i32) -> Result<()>;
+    /// This function starts the stepper `step`. The board sends a
+    /// `FirmataMessage::StepperMoveComplete` once the move is done.
+    fn stepper_step(&mut self, step: &StepperMove) -> Result<()>;
+    /// This function waits until the stepper `device` completed its move.
+    /// Messages that arrive in the meantime are applied as usual.
+    fn stepper_wait(&mut self, device: i32, timeout: Duration) -> Result<()>;
+    /// This function reads from the firmata device, parses one firmata
+    /// message, applies it to the board state and returns it. It fails with
+    /// `Error::Timeout` if no complete message arrives within `timeout`.
+    fn read_and_decode(&mut self) -> Result<FirmataMessage>;
 }
 
 /// A structure representing a firmata board.
 pub struct Board<T: io::Read+io::Write> {
     pub connection: Box<T>,
     pub pins: Vec<Pin>,
-    pub i2c_data: Vec<I2CReply>,
+    pub i2c_data: HashMap<(i32, i32), VecDeque<I2CReply>>,
+    pub string_data: Vec<String>,
+    pub completed_moves: Vec<u8>,
     pub protocol_version: String,
     pub firmware_name: String,
     pub firmware_version: String,
+    pub sampling_interval: Duration,
+    pub timeout: Duration,
+    decoder: Decoder,
+    protocol: (u8, u8),
+    digital_reporting: Reporting,
+    analog_reporting: Reporting,
 }
 
 impl<T: io::Read+io::Write> Board<T> {
-    /// Creates a new `Board` given an `io::Read+io::Write`.
+    /// Creates a new `Board` given an `io::Read+io::Write` and runs the
+    /// default handshake of `BoardBuilder`.
     pub fn new(connection: Box<T>) -> Result<Board<T>> {
-        let mut b = Board {
-            connection: connection,
+        BoardBuilder::new().build(connection)
+    }
+
+    /// Creates a `Board` on `connection` without talking to the device.
+    pub(crate) fn from_connection(connection: Box<T>) -> Board<T> {
+        Board {
+            connection,
             firmware_name: String::new(),
             firmware_version: String::new(),
             protocol_version: String::new(),
+            sampling_interval: Duration::from_millis(DEFAULT_SAMPLING_INTERVAL),
+            timeout: Duration::from_millis(DEFAULT_TIMEOUT),
             pins: vec![],
-            i2c_data: vec![],
-        };
+            i2c_data: HashMap::new(),
+            string_data: vec![],
+            completed_moves: vec![],
+            decoder: Decoder::new(),
+            protocol: (0, 0),
+            digital_reporting: Reporting::new(),
+            analog_reporting: Reporting::new(),
+        }
+    }
+
+    /// Returns the index of `pin` in `pins`, or `Error::InvalidPin` if the
+    /// board did not report such a pin in its capabilities.
+    fn pin_index(&self, pin: i32) -> Result<usize> {
+        if pin < 0 || pin as usize >= self.pins.len() {
+            return Err(Error::InvalidPin(pin));
+        }
+        Ok(pin as usize)
+    }
 
-        try!(b.query_firmware());
-        try!(b.read_and_decode());
-        try!(b.read_and_decode());
-        try!(b.query_capabilities());
-        try!(b.read_and_decode());
-        try!(b.query_analog_mapping());
-        try!(b.read_and_decode());
-        try!(b.report_digital(0, 1));
-        try!(b.report_digital(1, 1));
+    /// Sets the mode of the pin at `index` and follows StandardFirmata, which
+    /// turns reporting of an analog pin's channel on when the pin enters
+    /// analog mode and off when it leaves it.
+    fn update_mode(&mut self, index: usize, mode: PinMode) {
+        self.pins[index].set_mode(mode);
+        if let Some(channel) = self.pins[index].analog_channel {
+            if channel > 15 {
+                return;
+            }
+            if mode == PinMode::Analog {
+                self.analog_reporting.add_pin(channel, index);
+            } else {
+                self.analog_reporting.set(channel, false);
+            }
+        }
+    }
 
-        return Ok(b);
+    /// Encodes `commands` and writes them to the connection in a single
+    /// write.
+    pub fn send(&mut self, commands: &[Command]) -> Result<()> {
+        let mut buf = vec![];
+        for command in commands {
+            command.encode(&mut buf);
+        }
+        Ok(self.connection.write_all(&buf)?)
     }
+
+    /// Updates the board state from a decoded `message`.
+    fn apply(&mut self, message: &FirmataMessage) -> Result<()> {
+        match *message {
+            FirmataMessage::ProtocolVersion { major, minor } => {
+                self.protocol = (major, minor);
+                self.protocol_version = format!("{}.{}", major, minor);
+            },
+            FirmataMessage::AnalogMessage { channel, value } => {
+                if let Some(pin) = self.pins.iter_mut().find(|p| p.analog_channel == Some(channel)) {
+                    pin.report(value);
+                }
+            },
+            FirmataMessage::DigitalMessage { port, value } => {
+                for i in 0..8 {
+                    let pin = (8 * port as usize) + i;
+
+                    if self.pins.len() > pin
+                        && (self.pins[pin].mode == PinMode::Input
+                            || self.pins[pin].mode == PinMode::Pullup) {
+                        self.pins[pin].report((value >> (i & 0x07)) & 0x01);
+                    }
+                }
+            },
+            FirmataMessage::CapabilityResponse { ref pins } => {
+                // Keep a previously received analog mapping.
+                let channels: Vec<Option<u8>> = self.pins.iter().map(|p| p.analog_channel).collect();
+                self.pins = pins.iter().enumerate().map(|(i, modes)| {
+                    Pin::new(modes.clone(), channels.get(i).cloned().unwrap_or(None))
+                }).collect();
+            },
+            FirmataMessage::AnalogMappingResponse { ref channels } => {
+                for (pin, channel) in self.pins.iter_mut().zip(channels) {
+                    pin.analog_channel = *channel;
+                }
+            },
+            FirmataMessage::ReportFirmware { major, minor, ref name } => {
+                self.firmware_version = format!("{}.{}", major, minor);
+                self.firmware_name = name.clone();
+            },
+            FirmataMessage::I2CReply(ref reply) => {
+                self.i2c_data
+                    .entry((reply.address, reply.register))
+                    .or_default()
+                    .push_back(reply.clone());
+            },
+            FirmataMessage::UnknownSysex { id, ref data } => {
+                return Err(Error::UnknownSysex { id, payload: data.clone() });
+            },
+            FirmataMessage::PinStateResponse { pin, mode, state } => {
+                let index = pin as usize;
+                if index < self.pins.len() {
+                    self.update_mode(index, mode);
+                    self.pins[index].report(state);
+                }
+            },
+            FirmataMessage::StringData(ref text) => {
+                self.string_data.push(text.clone());
+            },
+            FirmataMessage::StepperMoveComplete { device } => {
+                self.completed_moves.push(device);
+            },
+        }
+        Ok(())
+    }
+
+    /// Reads from the connection until the decoder yields a complete
+    /// message, or fails with `Error::Timeout` once `timeout` has elapsed.
+    fn read_message(&mut self) -> Result<FirmataMessage> {
+        let deadline = Instant::now() + self.timeout;
+
+        loop {
+            if let Some(message) = self.decoder.decode()? {
+                return Ok(message);
+            }
+            if Instant::now() >= deadline {
+                return Err(Error::Timeout);
+            }
+            self.fill()?;
+        }
+    }
+
+    /// Decodes and applies messages until `done` returns true for one of
+    /// them, or fails with `Error::Timeout` once `timeout` has elapsed.
+    /// Framing errors and unknown sysex messages are skipped.
+    ///
+    /// The deadline is only checked between reads, so the connection needs a
+    /// read timeout of its own for this to return when the board is silent.
+    fn wait_for<F>(&mut self, timeout: Duration, mut done: F) -> Result<()>
+        where F: FnMut(&mut Self, &FirmataMessage) -> bool
+    {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            loop {
+                let message = match self.decoder.decode() {
+                    Ok(Some(message)) => message,
+                    Ok(None) => break,
+                    Err(Error::Framing(_)) => continue,
+                    Err(e) => return Err(e),
+                };
+                match self.apply(&message) {
+                    Ok(()) | Err(Error::UnknownSysex { .. }) => {},
+                    Err(e) => return Err(e),
+                }
+                if done(self, &message) {
+                    return Ok(());
+                }
+            }
+
+            if Instant::now() >= deadline {
+                return Err(Error::Timeout);
+            }
+            self.fill()?;
+        }
+    }
+
+    /// Reads whatever is available from the connection into the decoder.
+    /// A read that times out is not an error; sockets report a read timeout
+    /// as `WouldBlock` on some platforms. A closed connection is.
+    ///
+    /// After a read that came back empty it backs off briefly, so callers
+    /// polling a connection whose reads time out immediately don't spin.
+    fn fill(&mut self) -> Result<()> {
+        let mut buf = [0u8; 256];
+
+        match self.connection.read(&mut buf) {
+            Ok(0) => Err(io::Error::new(ErrorKind::UnexpectedEof, "connection closed").into()),
+            Ok(n) => {
+                self.decoder.push(&buf[..n]);
+                Ok(())
+            },
+            Err(e) => match e.kind() {
+                ErrorKind::Interrupted => Ok(()),
+                ErrorKind::TimedOut | ErrorKind::WouldBlock => {
+                    thread::sleep(Duration::from_millis(1));
+                    Ok(())
+                },
+                _ => Err(e.into()),
+            },
+        }
+    }
+
 }
 
 impl<T:io::Read+io::Write> Firmata for Board<T> {
@@ -193,243 +628,377 @@ impl<T:io::Read+io::Write> Firmata for Board<T> {
     fn firmware_version(&mut self) -> &String {
         &self.firmware_version
     }
-    fn i2c_data(&mut self) -> &mut Vec<I2CReply> {
+    fn sampling_interval(&mut self) -> Duration {
+        self.sampling_interval
+    }
+    fn timeout(&mut self) -> Duration {
+        self.timeout
+    }
+    fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+    fn i2c_data(&mut self) -> &mut HashMap<(i32, i32), VecDeque<I2CReply>> {
         &mut self.i2c_data
     }
+    fn string_data(&mut self) -> &mut Vec<String> {
+        &mut self.string_data
+    }
+
+    fn completed_moves(&mut self) -> &mut Vec<u8> {
+        &mut self.completed_moves
+    }
     fn query_analog_mapping(&mut self) -> Result<()> {
-        self.connection
-            .write(&mut [START_SYSEX, ANALOG_MAPPING_QUERY, END_SYSEX])
-            .map(|_|())
+        self.send(&[Command::AnalogMappingQuery])?;
+        let timeout = self.timeout;
+        self.wait_for(timeout, |_, message| {
+            matches!(*message, FirmataMessage::AnalogMappingResponse { .. })
+        })
+    }
+
+    fn analog_channel(&mut self, pin: i32) -> Option<u8> {
+        let index = self.pin_index(pin).ok()?;
+        self.pins[index].analog_channel
+    }
+
+    fn analog_pin(&mut self, channel: u8) -> Option<i32> {
+        self.pins
+            .iter()
+            .position(|p| p.analog_channel == Some(channel))
+            .map(|pin| pin as i32)
     }
 
     fn query_capabilities(&mut self) -> Result<()> {
-        self.connection
-            .write(&mut [START_SYSEX, CAPABILITY_QUERY, END_SYSEX])
-            .map(|_|())
+        self.send(&[Command::CapabilityQuery])?;
+        let timeout = self.timeout;
+        self.wait_for(timeout, |_, message| {
+            matches!(*message, FirmataMessage::CapabilityResponse { .. })
+        })
     }
 
     fn query_firmware(&mut self) -> Result<()> {
-        self.connection
-            .write(&mut [START_SYSEX, REPORT_FIRMWARE, END_SYSEX])
-            .map(|_|())
+        self.send(&[Command::ReportVersion, Command::ReportFirmware])?;
+        let timeout = self.timeout;
+        let mut version = false;
+        let mut firmware = false;
+        self.wait_for(timeout, |_, message| {
+            match *message {
+                FirmataMessage::ProtocolVersion { .. } => version = true,
+                FirmataMessage::ReportFirmware { .. } => firmware = true,
+                _ => {},
+            }
+            version && firmware
+        })
+    }
+
+    fn query_pin_state(&mut self, pin: i32) -> Result<()> {
+        let index = self.pin_index(pin)?;
+        self.send(&[Command::PinStateQuery { pin: index as u8 }])?;
+        let timeout = self.timeout;
+        self.wait_for(timeout, |_, message| {
+            match *message {
+                FirmataMessage::PinStateResponse { pin, .. } => pin as usize == index,
+                _ => false,
+            }
+        })
+    }
+
+    fn refresh_pin_states(&mut self) -> Result<()> {
+        let queries: Vec<Command> = (0..self.pins.len())
+            .map(|pin| Command::PinStateQuery { pin: pin as u8 })
+            .collect();
+        let mut pending = vec![true; queries.len()];
+        let mut remaining = queries.len();
+
+        if remaining == 0 {
+            return Ok(());
+        }
+        self.send(&queries)?;
+
+        let timeout = self.timeout;
+        self.wait_for(timeout, |_, message| {
+            if let FirmataMessage::PinStateResponse { pin, .. } = *message {
+                if pending.get(pin as usize) == Some(&true) {
+                    pending[pin as usize] = false;
+                    remaining -= 1;
+                }
+            }
+            remaining == 0
+        })
+    }
+
+    fn set_sampling_interval(&mut self, interval: Duration) -> Result<()> {
+        let millis = interval.as_millis().max(1);
+        if millis > 0x3FFF {
+            return Err(Error::OutOfRange("sampling interval"));
+        }
+
+        self.send(&[Command::SamplingInterval { interval: millis as u16 }])?;
+        self.sampling_interval = Duration::from_millis(millis as u64);
+        Ok(())
+    }
+
+    fn send_string(&mut self, text: &str) -> Result<()> {
+        self.send(&[Command::StringData(text.to_string())])
     }
 
     fn i2c_config(&mut self, delay: i32) -> Result<()> {
-        self.connection.write(
-            &mut [
-                START_SYSEX,
-                I2C_CONFIG,
-                (delay & 0xFF) as u8,
-                (delay >> 8 & 0xFF) as u8,
-                END_SYSEX
-            ]
-        ).map(|_|())
+        if !(0..=0x3FFF).contains(&delay) {
+            return Err(Error::OutOfRange("i2c delay"));
+        }
+        self.send(&[Command::I2CConfig { delay: delay as u16 }])
     }
 
     fn i2c_read(&mut self, address: i32, size: i32) -> Result<()> {
-        self.connection.write(
-            &mut [
-                START_SYSEX,
-                I2C_REQUEST,
-                address as u8,
-                (I2C_MODE_READ << 3),
-                ((size as u8) & 0x7F),
-                (((size) >> 7) & 0x7F) as u8,
-                END_SYSEX
-            ]
-        ).map(|_|())
+        self.i2c_request(&I2CRequest::read(address as u16, size as u16))
     }
 
     fn i2c_write(&mut self, address: i32, data: &[u8]) -> Result<()> {
-        let mut buf = vec![];
+        self.i2c_request(&I2CRequest::write(address as u16, data))
+    }
+
+    fn i2c_read_reply(&mut self, address: i32, register: Option<i32>, size: i32,
+                      timeout: Duration) -> Result<I2CReply> {
+        let mut request = I2CRequest::read(address as u16, size as u16);
+        if let Some(register) = register {
+            request = request.register(register as u16);
+        }
+        self.i2c_request(&request)?;
+
+        // Without a register StandardFirmata answers with register 255, so
+        // any register of the device matches.
+        let mut reply = None;
+        self.wait_for(timeout, |board, message| match *message {
+            FirmataMessage::I2CReply(ref r)
+                if r.address == address && register.is_none_or(|reg| reg == r.register) => {
+                if let Some(queue) = board.i2c_data.get_mut(&(r.address, r.register)) {
+                    queue.pop_back();
+                }
+                reply = Some(r.clone());
+                true
+            },
+            _ => false,
+        })?;
+        Ok(reply.unwrap())
+    }
+
+    fn i2c_request(&mut self, request: &I2CRequest) -> Result<()> {
+        request.validate()?;
+        self.send(&[Command::I2CRequest(request.clone())])
+    }
+
+    fn report_digital(&mut self, port: i32, state: i32) -> Result<()> {
+        if !(0..=15).contains(&port) {
+            return Err(Error::OutOfRange("digital port"));
+        }
+        let port = port as u8;
+        self.digital_reporting.set(port, state != 0);
+        self.send(&[Command::ReportDigital { port, enabled: state != 0 }])
+    }
+
+    fn report_analog(&mut self, channel: i32, state: i32) -> Result<()> {
+        if !(0..=15).contains(&channel) {
+            return Err(Error::OutOfRange("analog channel"));
+        }
+        let channel = channel as u8;
+        self.analog_reporting.set(channel, state != 0);
+        self.send(&[Command::ReportAnalog { channel, enabled: state != 0 }])
+    }
 
-        buf.push(START_SYSEX);
-        buf.push(I2C_REQUEST);
-        buf.push(address as u8);
-        buf.push(I2C_MODE_WRITE << 3);
+    fn enable_reporting(&mut self, pin: i32) -> Result<()> {
+        let index = self.pin_index(pin)?;
 
-        for i in data.iter() {
-            buf.push(i & 0x7F);
-            buf.push((((*i as i32) >> 7) & 0x7F) as u8);
+        // The firmware starts analog-capable pins in analog mode, so such a
+        // pin is analog until its mode is set or reported otherwise.
+        let analog = if self.pins[index].mode_known {
+            self.pins[index].mode == PinMode::Analog
+        } else {
+            self.pins[index].analog_channel.is_some()
+        };
+        if analog {
+            let channel = match self.pins[index].analog_channel {
+                Some(channel) if channel <= 15 => channel,
+                _ => return Err(Error::UnsupportedMode { pin, mode: PinMode::Analog }),
+            };
+            if self.analog_reporting.add_pin(channel, index) {
+                self.send(&[Command::ReportAnalog { channel, enabled: true }])?;
+            }
+            return Ok(());
+        }
+
+        let digital = self.pins[index]
+            .modes
+            .iter()
+            .any(|m| m.mode == PinMode::Input || m.mode == PinMode::Pullup);
+        if !digital {
+            return Err(Error::UnsupportedMode { pin, mode: PinMode::Input });
         }
+        let port = (index / 8) as u8;
+        if self.digital_reporting.add_pin(port, index) {
+            self.send(&[Command::ReportDigital { port, enabled: true }])?;
+        }
+        Ok(())
+    }
 
-        buf.push(END_SYSEX);
+    fn disable_reporting(&mut self, pin: i32) -> Result<()> {
+        let index = self.pin_index(pin)?;
+        let mut commands = vec![];
 
-        self.connection.write(&mut buf[..]).map(|_|())
+        let port = (index / 8) as u8;
+        if self.digital_reporting.remove_pin(port, index) {
+            commands.push(Command::ReportDigital { port, enabled: false });
+        }
+        if let Some(channel) = self.pins[index].analog_channel {
+            if self.analog_reporting.remove_pin(channel, index) {
+                commands.push(Command::ReportAnalog { channel, enabled: false });
+            }
+        }
+
+        if commands.is_empty() {
+            return Ok(());
+        }
+        self.send(&commands)
     }
 
-    fn report_digital(&mut self, pin: i32, state: i32) -> Result<()> {
-        self.connection.write(
-            &mut [
-                REPORT_DIGITAL | pin as u8,
-                state as u8
-            ]
-        ).map(|_|())
+    fn reporting_ports(&mut self) -> Vec<u8> {
+        self.digital_reporting.enabled()
     }
 
-    fn report_analog(&mut self, pin: i32, state: i32) -> Result<()> {
-        self.connection.write(
-            &mut [
-                REPORT_ANALOG | pin as u8,
-                state as u8
-            ]
-        ).map(|_|())
+    fn reporting_channels(&mut self) -> Vec<u8> {
+        self.analog_reporting.enabled()
     }
 
     fn analog_write(&mut self, pin: i32, level: i32) -> Result<()> {
-        self.pins[pin as usize].value = level;
+        let index = self.pin_index(pin)?;
+        // EXTENDED_ANALOG sends the pin in 7 bits and has no sign.
+        if index > 127 {
+            return Err(Error::OutOfRange("extended analog pin"));
+        }
+        if level < 0 {
+            return Err(Error::OutOfRange("analog level"));
+        }
+        self.pins[index].command(level);
 
-        self.connection.write(
-            &mut [
-                ANALOG_MESSAGE | pin as u8,
-                (level & 0x7f) as u8,
-                ((level >> 7) & 0x7f) as u8
-            ]
-        ).map(|_|())
+        // ANALOG_MESSAGE only has room for pins 0-15 and 14-bit values.
+        if index > 15 || level as u32 > 0x3FFF {
+            self.send(&[Command::ExtendedAnalog { pin: index as u8, value: level as u32 }])
+        } else {
+            self.send(&[Command::AnalogMessage { pin: index as u8, value: level as u16 }])
+        }
     }
 
     fn digital_write(&mut self, pin: i32, level: i32) -> Result<()> {
-        let port = (pin as f64 / 8f64).floor() as usize;
-        let mut value = 0i32;
-        let mut i = 0;
+        if self.protocol >= (2, 5) {
+            return self.set_digital_pin_value(pin, level);
+        }
 
-        self.pins[pin as usize].value = level;
+        let index = self.pin_index(pin)?;
+        let port = index / 8;
+        let mut value = 0u8;
 
-        while i < 8 {
-            if self.pins[8*port+i].value != 0 {
-                value = value | (1 << i)
+        self.pins[index].command(level);
+
+        // Only outputs are driven by the port value; the bits of the other
+        // pins would turn pull-ups on or off on older firmware.
+        for (i, p) in self.pins.iter().skip(8 * port).take(8).enumerate() {
+            if p.mode == PinMode::Output && p.commanded_value != 0 {
+                value |= 1 << i;
             }
-            i += 1;
         }
 
-        self.connection.write(
-            &mut [
-                DIGITAL_MESSAGE | port as u8,
-                (value & 0x7f) as u8,
-                ((value >> 7) & 0x7f) as u8
-            ]
-        ).map(|_|())
+        self.send(&[Command::DigitalMessage { port: port as u8, value }])
     }
 
-    fn set_pin_mode(&mut self, pin: i32, mode: u8) -> Result<()> {
-        self.pins[pin as usize].mode = mode;
-        self.connection.write(&mut [PIN_MODE, pin as u8, mode as u8]).map(|_|())
+    fn set_digital_pin_value(&mut self, pin: i32, level: i32) -> Result<()> {
+        if self.protocol < (2, 5) {
+            return Err(Error::UnsupportedProtocol { required: (2, 5), reported: self.protocol });
+        }
+        let index = self.pin_index(pin)?;
+        self.pins[index].command(level);
+        self.send(&[Command::SetDigitalPinValue { pin: index as u8, value: level != 0 }])
     }
 
-    fn read_and_decode(&mut self) -> Result<()> {
-        let mut buf = try!(read(&mut self.connection, 3));
-        match buf[0] {
-            PROTOCOL_VERSION => {
-                self.protocol_version = format!("{:o}.{:o}", buf[1], buf[2]);
-                Ok(())
-            },
-            ANALOG_MESSAGE...0xEF => {
-                let value = (buf[1] as i32) | ((buf[2] as i32) << 7);
-                let pin = ((buf[0] as i32) & 0x0F) + 14;
+    fn set_pin_mode(&mut self, pin: i32, mode: PinMode) -> Result<()> {
+        let index = self.pin_index(pin)?;
+        if !self.pins[index].modes.iter().any(|m| m.mode == mode) {
+            return Err(Error::UnsupportedMode { pin, mode });
+        }
 
-                if self.pins.len() as i32 > pin {
-                    self.pins[pin as usize].value = value;
-                }
-                Ok(())
-            },
-            DIGITAL_MESSAGE...0x9F => {
-                let port = (buf[0] as i32) & 0x0F;
-                let value = (buf[1] as i32) | ((buf[2] as i32) << 7);
+        self.update_mode(index, mode);
+        self.send(&[Command::SetPinMode { pin: pin as u8, mode }])
+    }
 
-                for i in 0..8 {
-                    let pin = (8 * port) + i;
+    fn servo_config(&mut self, pin: i32, min_pulse: i32, max_pulse: i32) -> Result<()> {
+        let index = self.pin_index(pin)?;
+        if !self.pins[index].modes.iter().any(|m| m.mode == PinMode::Servo) {
+            return Err(Error::UnsupportedMode { pin, mode: PinMode::Servo });
+        }
+        if min_pulse < 0 || min_pulse > max_pulse || max_pulse > 0x3FFF {
+            return Err(Error::OutOfRange("servo pulse width"));
+        }
 
-                    if self.pins.len() as i32 > pin {
-                        if self.pins[pin as usize].mode == INPUT {
-                            self.pins[pin as usize].value = (value >> (i & 0x07)) & 0x01;
-                        }
-                    }
-                }
-                Ok(())
-            },
-            START_SYSEX => {
-                loop {
-                    let message = try!(read(&mut *self.connection, 1));
-                    buf.push(message[0]);
-                    if message[0] == END_SYSEX {
-                        break;
-                    }
-                }
-                match buf[1] {
-                    ANALOG_MAPPING_RESPONSE => {
-                        if self.pins.len() > 0 {
-                           let mut i = 2;
-                           while i < buf.len()-1 {
-                               if buf[i] != 127u8 {
-                                   self.pins[i-2].analog = true;
-                               }
-                               i += 1;
-                           }
-                        }
-                        Ok(())
-                    },
-                    CAPABILITY_RESPONSE => {
-                        let mut pin = 0;
-                        let mut i = 2;
-                        self.pins = vec![];
-                        self.pins.push(Pin{
-                            modes: vec![],
-                            analog: false,
-                            value: 0,
-                            mode: 0,
-                        });
-                        while i < buf.len()-1 {
-                            if buf[i] == 127u8 {
-                                pin += 1;
-                                i += 1;
-                                self.pins.push(Pin{
-                                    modes: vec![],
-                                    analog: false,
-                                    value: 0,
-                                    mode: 0,
-                                });
-                                continue;
-                            }
-                            self.pins[pin].modes.push(Mode {
-                                mode: buf[i],
-                                resolution: buf[i+1]
-                            });
-                            i += 2;
-                        }
-                        Ok(())
-                    },
-                    REPORT_FIRMWARE => {
-                        self.firmware_version = format!("{:o}.{:o}", buf[2], buf[3]);
-                        self.firmware_name = str::from_utf8(&buf[4..buf.len()-1]).unwrap().to_string();
-                        Ok(())
-                    },
-                    I2C_REPLY => {
-                        let len = buf.len();
-                        let mut reply = I2CReply{
-                            address:  (buf[2] as i32) | ((buf[3] as i32) << 7),
-                            register: (buf[4] as i32) | ((buf[5] as i32) << 7),
-                            data:     vec![buf[6] | buf[7]<<7],
-                        };
-                        let mut i = 8;
-
-                        while i < len-1 {
-                            if buf[i] == 0xF7 {
-                                break
-                            }
-                            if i+2 > len {
-                                break
-                            }
-                            reply.data.push(buf[i] | buf[i+1] << 7);
-                            i += 2;
-                        }
-                        self.i2c_data.push(reply);
-                        Ok(())
-                    },
-                    _ => Err(Error::new(ErrorKind::Other, "unknown sysex code")),
+        self.update_mode(index, PinMode::Servo);
+        self.send(&[Command::ServoConfig {
+            pin: index as u8,
+            min_pulse: min_pulse as u16,
+            max_pulse: max_pulse as u16,
+        }])
+    }
+
+    fn stepper_config(&mut self, device: i32, interface: StepperInterface,
+                      steps_per_revolution: i32) -> Result<()> {
+        if device < 0 || device >= MAX_STEPPERS as i32 {
+            return Err(Error::OutOfRange("stepper device"));
+        }
+        if !(0..=0x3FFF).contains(&steps_per_revolution) {
+            return Err(Error::OutOfRange("stepper steps per revolution"));
+        }
+        let pins = interface.pins();
+        for pin in &pins {
+            let pin = *pin as i32;
+            let index = self.pin_index(pin)?;
+            if !self.pins[index].modes.iter().any(|m| m.mode == PinMode::Stepper) {
+                return Err(Error::UnsupportedMode { pin, mode: PinMode::Stepper });
+            }
+        }
+
+        for pin in pins {
+            self.update_mode(pin as usize, PinMode::Stepper);
+        }
+        self.send(&[Command::StepperConfig {
+            device: device as u8,
+            interface,
+            steps_per_revolution: steps_per_revolution as u16,
+        }])
+    }
+
+    fn stepper_step(&mut self, step: &StepperMove) -> Result<()> {
+        step.validate()?;
+        // A completion left over from an earlier move would end a wait for
+        // this one early.
+        self.completed_moves.retain(|device| *device != step.device);
+        self.send(&[Command::StepperStep(step.clone())])
+    }
+
+    fn stepper_wait(&mut self, device: i32, timeout: Duration) -> Result<()> {
+        let done = |board: &mut Self| {
+            match board.completed_moves.iter().position(|d| *d as i32 == device) {
+                Some(i) => {
+                    board.completed_moves.remove(i);
+                    true
                 }
-            },
-            _ => Err(Error::new(ErrorKind::Other,"bad byte")),
+                None => false,
+            }
+        };
+        if done(self) {
+            return Ok(());
         }
+        self.wait_for(timeout, |board, _| done(board))
+    }
+
+    fn read_and_decode(&mut self) -> Result<FirmataMessage> {
+        let message = self.read_message()?;
+        self.apply(&message)?;
+        Ok(message)
     }
 
 }
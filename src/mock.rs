@@ -0,0 +1,103 @@
+//! This module contains an in-memory connection for testing code that talks
+//! to a `Board` without a device attached.
+use std::collections::VecDeque;
+use std::io;
+use std::thread;
+use std::time::Duration;
+
+use {FirmataMessage, Profile};
+
+/// A connection that records everything written to it and replays scripted
+/// responses.
+///
+/// Each queued response is returned by its own read, so the board sees the
+/// same chunking a real connection would produce. Once the script runs out,
+/// reads fail with `ErrorKind::TimedOut` after a short pause, like an idle
+/// serial port.
+#[derive(Debug, Default, Clone)]
+pub struct MockConnection {
+    pub written: Vec<u8>,
+    responses: VecDeque<Vec<u8>>,
+}
+
+impl MockConnection {
+    /// Creates a new `MockConnection` with an empty script.
+    pub fn new() -> MockConnection {
+        MockConnection {
+            written: vec![],
+            responses: VecDeque::new(),
+        }
+    }
+
+    /// Creates a `MockConnection` scripted to answer the handshake of
+    /// `Board::new` like the device described by `profile`.
+    pub fn with_profile(profile: &Profile) -> MockConnection {
+        let mut connection = MockConnection::new();
+        for message in profile.handshake() {
+            connection.respond(&message);
+        }
+        connection
+    }
+
+    /// Creates a `MockConnection` that answers the handshake like an Arduino
+    /// Uno running StandardFirmata.
+    pub fn uno() -> MockConnection {
+        MockConnection::with_profile(&Profile::uno())
+    }
+
+    /// Creates a `MockConnection` that answers the handshake like an Arduino
+    /// Mega 2560 running StandardFirmata.
+    pub fn mega() -> MockConnection {
+        MockConnection::with_profile(&Profile::mega())
+    }
+
+    /// Queues `message` to be returned by a later read.
+    pub fn respond(&mut self, message: &FirmataMessage) {
+        self.respond_bytes(&message.to_bytes());
+    }
+
+    /// Queues raw `bytes` to be returned by a later read.
+    pub fn respond_bytes(&mut self, bytes: &[u8]) {
+        self.responses.push_back(bytes.to_vec());
+    }
+
+    /// Returns the number of scripted responses that have not been read.
+    pub fn pending(&self) -> usize {
+        self.responses.len()
+    }
+
+    /// Returns and clears the bytes written so far.
+    pub fn take_written(&mut self) -> Vec<u8> {
+        self.written.split_off(0)
+    }
+}
+
+impl io::Read for MockConnection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut chunk = match self.responses.pop_front() {
+            Some(chunk) => chunk,
+            None => {
+                thread::sleep(Duration::from_millis(1));
+                return Err(io::Error::new(io::ErrorKind::TimedOut, "no scripted response"));
+            }
+        };
+
+        let n = buf.len().min(chunk.len());
+        buf[..n].copy_from_slice(&chunk[..n]);
+        if n < chunk.len() {
+            self.responses.push_front(chunk.split_off(n));
+        }
+        Ok(n)
+    }
+}
+
+impl io::Write for MockConnection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.written.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,152 @@
+//! This module contains descriptions of common StandardFirmata boards.
+use {FirmataMessage, Mode, PinMode};
+
+/// A description of what a firmata device reports about itself.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Profile {
+    pub firmware_name: String,
+    pub firmware_version: (u8, u8),
+    pub protocol_version: (u8, u8),
+    /// The modes supported by each pin, indexed by pin number.
+    pub pins: Vec<Vec<Mode>>,
+    /// The analog channel of each pin, indexed by pin number.
+    pub analog_channels: Vec<Option<u8>>,
+}
+
+impl Profile {
+    /// Returns the profile of an Arduino Uno running StandardFirmata 2.5.
+    /// Pins 0 and 1 carry the serial connection and report no modes.
+    pub fn uno() -> Profile {
+        let pins = (0..20u8)
+            .map(|pin| {
+                let mut modes = vec![];
+                if pin >= 2 {
+                    modes.extend(digital_modes());
+                }
+                if pin >= 14 {
+                    modes.push(mode(PinMode::Analog, 10));
+                }
+                if [3, 5, 6, 9, 10, 11].contains(&pin) {
+                    modes.push(mode(PinMode::Pwm, 8));
+                }
+                // IS_PIN_SERVO is limited to the first MAX_SERVOS (12) digital
+                // pins after the serial pins.
+                if (2..14).contains(&pin) {
+                    modes.push(mode(PinMode::Servo, 14));
+                }
+                if pin == 18 || pin == 19 {
+                    modes.push(mode(PinMode::I2C, 1));
+                }
+                modes
+            })
+            .collect();
+
+        Profile {
+            firmware_name: "StandardFirmata.ino".to_string(),
+            firmware_version: (2, 5),
+            protocol_version: (2, 5),
+            pins,
+            analog_channels: (0..20u8).map(|pin| pin.checked_sub(14)).collect(),
+        }
+    }
+
+    /// Returns the profile of an Arduino Mega 2560 running StandardFirmata
+    /// 2.5.
+    pub fn mega() -> Profile {
+        // The serial resolution byte encodes the port and whether the pin is
+        // its RX (even) or TX (odd) line. Pins 0 and 1 carry the connection
+        // to the host and are not offered.
+        let serial = |pin: u8| match pin {
+            19 => Some(0x02),
+            18 => Some(0x03),
+            17 => Some(0x04),
+            16 => Some(0x05),
+            15 => Some(0x06),
+            14 => Some(0x07),
+            _ => None,
+        };
+        let pins = (0..70u8)
+            .map(|pin| {
+                let mut modes = vec![];
+                if pin >= 2 {
+                    modes.extend(digital_modes());
+                }
+                if pin >= 54 {
+                    modes.push(mode(PinMode::Analog, 10));
+                }
+                if (2..=13).contains(&pin) || (44..=46).contains(&pin) {
+                    modes.push(mode(PinMode::Pwm, 8));
+                }
+                // MAX_SERVOS is 48 on the Mega.
+                if (2..50).contains(&pin) {
+                    modes.push(mode(PinMode::Servo, 14));
+                }
+                if pin == 20 || pin == 21 {
+                    modes.push(mode(PinMode::I2C, 1));
+                }
+                if let Some(resolution) = serial(pin) {
+                    modes.push(mode(PinMode::Serial, resolution));
+                }
+                modes
+            })
+            .collect();
+
+        Profile {
+            firmware_name: "StandardFirmata.ino".to_string(),
+            firmware_version: (2, 5),
+            protocol_version: (2, 5),
+            pins,
+            analog_channels: (0..70u8).map(|pin| pin.checked_sub(54)).collect(),
+        }
+    }
+
+    /// Returns the protocol version the device sends on startup.
+    pub fn protocol_version_message(&self) -> FirmataMessage {
+        FirmataMessage::ProtocolVersion {
+            major: self.protocol_version.0,
+            minor: self.protocol_version.1,
+        }
+    }
+
+    /// Returns the device's answer to a firmware query.
+    pub fn report_firmware_message(&self) -> FirmataMessage {
+        FirmataMessage::ReportFirmware {
+            major: self.firmware_version.0,
+            minor: self.firmware_version.1,
+            name: self.firmware_name.clone(),
+        }
+    }
+
+    /// Returns the device's answer to a capability query.
+    pub fn capability_response(&self) -> FirmataMessage {
+        FirmataMessage::CapabilityResponse { pins: self.pins.clone() }
+    }
+
+    /// Returns the device's answer to an analog mapping query.
+    pub fn analog_mapping_response(&self) -> FirmataMessage {
+        FirmataMessage::AnalogMappingResponse { channels: self.analog_channels.clone() }
+    }
+
+    /// Returns the messages the device sends during the handshake of
+    /// `Board::new`, in order.
+    pub fn handshake(&self) -> Vec<FirmataMessage> {
+        vec![
+            self.protocol_version_message(),
+            self.report_firmware_message(),
+            self.capability_response(),
+            self.analog_mapping_response(),
+        ]
+    }
+}
+
+fn mode(mode: PinMode, resolution: u8) -> Mode {
+    Mode { mode, resolution }
+}
+
+fn digital_modes() -> Vec<Mode> {
+    vec![
+        mode(PinMode::Input, 1),
+        mode(PinMode::Pullup, 1),
+        mode(PinMode::Output, 1),
+    ]
+}
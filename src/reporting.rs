@@ -0,0 +1,60 @@
+//! This module contains the bookkeeping behind per-pin reporting.
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Tracks which digital ports or analog channels are reported and which
+/// pins asked for each of them.
+///
+/// A port or channel stays enabled while it was enabled directly or at
+/// least one of its pins still wants reports.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Reporting {
+    direct: BTreeSet<u8>,
+    pins: BTreeMap<u8, BTreeSet<usize>>,
+}
+
+impl Reporting {
+    pub(crate) fn new() -> Reporting {
+        Reporting::default()
+    }
+
+    pub(crate) fn is_enabled(&self, id: u8) -> bool {
+        self.direct.contains(&id) || self.pins.contains_key(&id)
+    }
+
+    /// Returns the enabled ports or channels in ascending order.
+    pub(crate) fn enabled(&self) -> Vec<u8> {
+        self.direct.union(&self.pins.keys().cloned().collect()).cloned().collect()
+    }
+
+    /// Enables or disables `id` directly. Disabling also forgets the pins
+    /// that asked for it, since the device stops reporting all of them.
+    pub(crate) fn set(&mut self, id: u8, enabled: bool) {
+        if enabled {
+            self.direct.insert(id);
+        } else {
+            self.direct.remove(&id);
+            self.pins.remove(&id);
+        }
+    }
+
+    /// Records that `pin` wants reports of `id`. Returns whether `id` has to
+    /// be enabled on the device.
+    pub(crate) fn add_pin(&mut self, id: u8, pin: usize) -> bool {
+        let was_enabled = self.is_enabled(id);
+        self.pins.entry(id).or_default().insert(pin);
+        !was_enabled
+    }
+
+    /// Records that `pin` no longer wants reports of `id`. Returns whether
+    /// `id` has to be disabled on the device.
+    pub(crate) fn remove_pin(&mut self, id: u8, pin: usize) -> bool {
+        let removed = match self.pins.get_mut(&id) {
+            Some(pins) => pins.remove(&pin),
+            None => false,
+        };
+        if self.pins.get(&id).is_some_and(|pins| pins.is_empty()) {
+            self.pins.remove(&id);
+        }
+        removed && !self.is_enabled(id)
+    }
+}
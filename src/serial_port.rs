@@ -0,0 +1,105 @@
+//! This module contains helpers for talking to boards over a serial port.
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serial;
+use serial::{SerialPort, SystemPort};
+
+use {Board, BoardBuilder, Result};
+
+/// Prefixes of the device names that USB serial adapters and boards with
+/// native USB show up as under `/dev`.
+const PORT_PREFIXES: &[&str] = &["ttyACM", "ttyUSB", "ttyAMA", "cu.usbmodem", "cu.usbserial",
+                                 "cu.wchusbserial"];
+
+/// A serial port that answered a firmware query.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DetectedBoard {
+    pub path: PathBuf,
+    pub firmware_name: String,
+}
+
+impl Board<SystemPort> {
+    /// Opens the serial port at `path` at `baud` 8N1 and runs the default
+    /// handshake of `BoardBuilder`.
+    pub fn open<P: AsRef<Path>>(path: P, baud: usize) -> Result<Board<SystemPort>> {
+        BoardBuilder::new().build(Box::new(open_port(path, baud)?))
+    }
+}
+
+/// Opens the serial port at `path` and configures it at `baud` 8N1 without
+/// flow control, as StandardFirmata expects.
+pub fn open_port<P: AsRef<Path>>(path: P, baud: usize) -> Result<SystemPort> {
+    let mut port = serial::open(path.as_ref().as_os_str()).map_err(io::Error::from)?;
+
+    port.reconfigure(&|settings| {
+        settings.set_baud_rate(baud_rate(baud))?;
+        settings.set_char_size(serial::Bits8);
+        settings.set_parity(serial::ParityNone);
+        settings.set_stop_bits(serial::Stop1);
+        settings.set_flow_control(serial::FlowNone);
+        Ok(())
+    }).map_err(io::Error::from)?;
+
+    Ok(port)
+}
+
+/// Returns the device paths under `/dev` that look like serial ports a
+/// board could be attached to, sorted by name.
+pub fn candidate_ports() -> Vec<PathBuf> {
+    let mut ports: Vec<PathBuf> = match fs::read_dir("/dev") {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| PORT_PREFIXES.iter().any(|p| name.starts_with(p)))
+            })
+            .collect(),
+        Err(_) => vec![],
+    };
+
+    ports.sort();
+    ports
+}
+
+/// Probes every candidate port with a firmware query at `baud` and returns
+/// the ones that answered within `timeout`. Boards that reset when the port
+/// is opened need a timeout long enough for them to boot.
+pub fn detect_boards(baud: usize, timeout: Duration) -> Vec<DetectedBoard> {
+    candidate_ports()
+        .into_iter()
+        .filter_map(|path| {
+            let port = open_port(&path, baud).ok()?;
+            let board = BoardBuilder::new()
+                .query_capabilities(false)
+                .query_analog_mapping(false)
+                .report_digital(&[])
+                .timeout(timeout)
+                .build(Box::new(port))
+                .ok()?;
+
+            Some(DetectedBoard { path, firmware_name: board.firmware_name })
+        })
+        .collect()
+}
+
+fn baud_rate(baud: usize) -> serial::BaudRate {
+    match baud {
+        110 => serial::Baud110,
+        300 => serial::Baud300,
+        600 => serial::Baud600,
+        1200 => serial::Baud1200,
+        2400 => serial::Baud2400,
+        4800 => serial::Baud4800,
+        9600 => serial::Baud9600,
+        19200 => serial::Baud19200,
+        38400 => serial::Baud38400,
+        57600 => serial::Baud57600,
+        115200 => serial::Baud115200,
+        baud => serial::BaudOther(baud),
+    }
+}
@@ -0,0 +1,66 @@
+//! This module contains a helper for driving hobby servos.
+use {Firmata, Result};
+
+/// A servo attached to a pin of a firmata board.
+///
+/// The handle only remembers the pin and pulse range; every write goes
+/// through the `Firmata` board passed to it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Servo {
+    pin: i32,
+    min_pulse: i32,
+    max_pulse: i32,
+}
+
+impl Servo {
+    /// The minimum pulse width in microseconds used by the Arduino `Servo`
+    /// library.
+    pub const DEFAULT_MIN_PULSE: i32 = 544;
+    /// The maximum pulse width in microseconds used by the Arduino `Servo`
+    /// library.
+    pub const DEFAULT_MAX_PULSE: i32 = 2400;
+
+    /// Attaches a servo to `pin` with the default pulse range.
+    pub fn attach<F: Firmata>(board: &mut F, pin: i32) -> Result<Servo> {
+        Servo::attach_with_range(board, pin, Servo::DEFAULT_MIN_PULSE, Servo::DEFAULT_MAX_PULSE)
+    }
+
+    /// Attaches a servo to `pin` that is driven with pulses between
+    /// `min_pulse` and `max_pulse` microseconds.
+    pub fn attach_with_range<F: Firmata>(board: &mut F, pin: i32, min_pulse: i32,
+                                         max_pulse: i32) -> Result<Servo> {
+        board.servo_config(pin, min_pulse, max_pulse)?;
+
+        Ok(Servo { pin, min_pulse, max_pulse })
+    }
+
+    /// Returns the pin the servo is attached to.
+    pub fn pin(&self) -> i32 {
+        self.pin
+    }
+
+    /// Returns the configured minimum pulse width in microseconds.
+    pub fn min_pulse(&self) -> i32 {
+        self.min_pulse
+    }
+
+    /// Returns the configured maximum pulse width in microseconds.
+    pub fn max_pulse(&self) -> i32 {
+        self.max_pulse
+    }
+
+    /// Moves the servo to `angle` degrees, clamped to 0-180. The firmware
+    /// maps the angle onto the configured pulse range.
+    pub fn write_angle<F: Firmata>(&self, board: &mut F, angle: i32) -> Result<()> {
+        board.analog_write(self.pin, angle.clamp(0, 180))
+    }
+
+    /// Drives the servo with a pulse of `micros` microseconds, clamped to the
+    /// configured pulse range.
+    ///
+    /// The firmware treats values below 544 as angles, so pulse ranges that
+    /// start below that cannot be fully reached this way.
+    pub fn write_microseconds<F: Firmata>(&self, board: &mut F, micros: i32) -> Result<()> {
+        board.analog_write(self.pin, micros.clamp(self.min_pulse, self.max_pulse))
+    }
+}
@@ -0,0 +1,698 @@
+//! This module contains a software firmata device for running a `Board`
+//! without hardware attached.
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use decoder::decode_bytes;
+use {Command, FirmataMessage, I2CMode, I2CReply, I2CRequest, PinMode, Profile, StepDirection,
+     StepperInterface, StepperMove};
+use {ANALOG_MAPPING_QUERY, ANALOG_MESSAGE, CAPABILITY_QUERY, DIGITAL_MESSAGE, END_SYSEX,
+     EXTENDED_ANALOG, I2C_10BIT_ADDRESS, I2C_AUTO_RESTART, I2C_CONFIG, I2C_REQUEST, PIN_MODE,
+     PIN_STATE_QUERY, PROTOCOL_VERSION, REPORT_ANALOG, REPORT_DIGITAL, REPORT_FIRMWARE,
+     SAMPLING_INTERVAL, SERVO_CONFIG, SET_DIGITAL_PIN_VALUE, START_SYSEX, STRING_DATA,
+     SYSTEM_RESET, STEPPER_CONFIG, STEPPER_DATA, STEPPER_STEP, DEFAULT_SAMPLING_INTERVAL,
+     MAX_STEPPERS};
+
+/// A virtual device on the simulated I2C bus.
+pub trait I2CDevice {
+    /// Receives the bytes of a write transaction. A read from a register is
+    /// preceded by a write of the register number.
+    fn write(&mut self, data: &[u8]);
+    /// Returns the bytes of a read transaction of `size` bytes.
+    fn read(&mut self, size: usize) -> Vec<u8>;
+}
+
+/// An I2C device with 256 byte-wide registers, like a small EEPROM.
+///
+/// The first byte of a write selects the register; the remaining bytes and
+/// any following reads start there and advance one register per byte.
+#[derive(Debug, Clone)]
+pub struct I2CMemory {
+    registers: Vec<u8>,
+    pointer: u8,
+}
+
+impl Default for I2CMemory {
+    fn default() -> I2CMemory {
+        I2CMemory::new()
+    }
+}
+
+impl I2CMemory {
+    /// Creates a new `I2CMemory` with every register set to zero.
+    pub fn new() -> I2CMemory {
+        I2CMemory {
+            registers: vec![0; 256],
+            pointer: 0,
+        }
+    }
+
+    /// Returns the value of `register`.
+    pub fn register(&self, register: u8) -> u8 {
+        self.registers[register as usize]
+    }
+
+    /// Sets `register` to `value`.
+    pub fn set_register(&mut self, register: u8, value: u8) {
+        self.registers[register as usize] = value;
+    }
+}
+
+impl I2CDevice for I2CMemory {
+    fn write(&mut self, data: &[u8]) {
+        if let Some((register, values)) = data.split_first() {
+            self.pointer = *register;
+            for value in values {
+                self.registers[self.pointer as usize] = *value;
+                self.pointer = self.pointer.wrapping_add(1);
+            }
+        }
+    }
+
+    fn read(&mut self, size: usize) -> Vec<u8> {
+        (0..size)
+            .map(|_| {
+                let value = self.registers[self.pointer as usize];
+                self.pointer = self.pointer.wrapping_add(1);
+                value
+            })
+            .collect()
+    }
+}
+
+/// The state of a pin as seen by the simulated device.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimulatedPin {
+    pub mode: PinMode,
+    /// The level of an input pin or the last value written to an output pin.
+    pub value: i32,
+}
+
+/// A software firmata device that behaves like StandardFirmata running on
+/// the board described by a `Profile`.
+///
+/// The simulator is itself the connection: bytes written to it are handled
+/// as commands and its answers are returned by later reads, so a `Board`
+/// can be created on it directly. Reports of analog inputs and continuous
+/// I2C reads are sent once per sampling interval while the board reads, or
+/// right away with `sample`. Once there is nothing left to send, reads fail
+/// with `ErrorKind::TimedOut` like an idle serial port.
+pub struct Simulator {
+    profile: Profile,
+    pins: Vec<SimulatedPin>,
+    digital_reporting: [bool; 16],
+    analog_reporting: [bool; 16],
+    sampling_interval: Duration,
+    last_sample: Instant,
+    i2c_devices: HashMap<u16, Box<dyn I2CDevice>>,
+    i2c_reads: Vec<I2CRequest>,
+    strings: Vec<String>,
+    steppers: HashMap<u8, i64>,
+    input: Vec<u8>,
+    output: VecDeque<u8>,
+}
+
+impl Simulator {
+    /// Creates a new `Simulator` of the board described by `profile`, in its
+    /// power-on state.
+    pub fn new(profile: Profile) -> Simulator {
+        let mut simulator = Simulator {
+            profile,
+            pins: vec![],
+            digital_reporting: [false; 16],
+            analog_reporting: [false; 16],
+            sampling_interval: Duration::from_millis(DEFAULT_SAMPLING_INTERVAL),
+            last_sample: Instant::now(),
+            i2c_devices: HashMap::new(),
+            i2c_reads: vec![],
+            strings: vec![],
+            steppers: HashMap::new(),
+            input: vec![],
+            output: VecDeque::new(),
+        };
+        simulator.reset();
+        simulator
+    }
+
+    /// Creates a `Simulator` of an Arduino Uno running StandardFirmata.
+    pub fn uno() -> Simulator {
+        Simulator::new(Profile::uno())
+    }
+
+    /// Creates a `Simulator` of an Arduino Mega 2560 running StandardFirmata.
+    pub fn mega() -> Simulator {
+        Simulator::new(Profile::mega())
+    }
+
+    /// Returns the profile of the simulated board.
+    pub fn profile(&self) -> &Profile {
+        &self.profile
+    }
+
+    /// Returns the state of every pin, indexed by pin number.
+    pub fn pins(&self) -> &[SimulatedPin] {
+        &self.pins
+    }
+
+    /// Returns the current sampling interval.
+    pub fn sampling_interval(&self) -> Duration {
+        self.sampling_interval
+    }
+
+    /// Returns whether reporting is enabled for a digital `port`.
+    pub fn is_reporting_digital(&self, port: u8) -> bool {
+        self.digital_reporting.get(port as usize).cloned().unwrap_or(false)
+    }
+
+    /// Returns whether reporting is enabled for an analog `channel`.
+    pub fn is_reporting_analog(&self, channel: u8) -> bool {
+        self.analog_reporting.get(channel as usize).cloned().unwrap_or(false)
+    }
+
+    /// Returns the text messages received from the board.
+    pub fn received_strings(&self) -> &[String] {
+        &self.strings
+    }
+
+    /// Returns the position in steps of the stepper `device`, counted
+    /// clockwise from where it was configured, or `None` if the device has
+    /// not been configured.
+    pub fn stepper_position(&self, device: u8) -> Option<i64> {
+        self.steppers.get(&device).cloned()
+    }
+
+    /// Attaches `device` to the I2C bus at `address`, replacing any device
+    /// already there.
+    pub fn attach_i2c_device<D: I2CDevice + 'static>(&mut self, address: u16, device: D) {
+        self.i2c_devices.insert(address, Box::new(device));
+    }
+
+    /// Drives the input `pin` high or low. Pins that are not in input or
+    /// pullup mode ignore the level. A report is sent if the port of the pin
+    /// changed and is being reported.
+    pub fn set_digital_input(&mut self, pin: u8, high: bool) {
+        let port = pin / 8;
+        let before = self.port_value(port);
+        match self.pins.get_mut(pin as usize) {
+            Some(p) if p.mode == PinMode::Input || p.mode == PinMode::Pullup => {
+                p.value = high as i32;
+            }
+            _ => return,
+        }
+
+        let after = self.port_value(port);
+        if after != before && self.is_reporting_digital(port) {
+            self.queue(&FirmataMessage::DigitalMessage { port, value: after as i32 });
+        }
+    }
+
+    /// Sets the reading of the analog input `pin`. Pins that are not in
+    /// analog mode ignore the value. Reported values are sent on the next
+    /// sample.
+    pub fn set_analog_input(&mut self, pin: u8, value: u16) {
+        if let Some(p) = self.pins.get_mut(pin as usize) {
+            if p.mode == PinMode::Analog {
+                p.value = value as i32;
+            }
+        }
+    }
+
+    /// Sends a text message to the board.
+    pub fn send_string(&mut self, text: &str) {
+        self.queue(&FirmataMessage::StringData(text.to_string()));
+    }
+
+    /// Sends the reports due every sampling interval: the value of each
+    /// reported analog channel followed by the result of each continuous I2C
+    /// read.
+    pub fn sample(&mut self) {
+        self.last_sample = Instant::now();
+
+        for channel in 0..16u8 {
+            if self.analog_reporting[channel as usize] {
+                self.report_analog_channel(channel);
+            }
+        }
+
+        let reads = self.i2c_reads.clone();
+        for request in &reads {
+            self.i2c_read(request);
+        }
+    }
+
+    /// Restores the power-on state: pins that can read analog inputs are in
+    /// analog mode, the other pins are outputs and nothing is reported.
+    /// Attached I2C devices are kept.
+    fn reset(&mut self) {
+        self.pins = self
+            .profile
+            .pins
+            .iter()
+            .map(|modes| {
+                let supports = |mode| modes.iter().any(|m| m.mode == mode);
+                let mode = if supports(PinMode::Analog) {
+                    PinMode::Analog
+                } else if supports(PinMode::Output) {
+                    PinMode::Output
+                } else {
+                    PinMode::Ignore
+                };
+                SimulatedPin { mode, value: 0 }
+            })
+            .collect();
+        self.digital_reporting = [false; 16];
+        self.analog_reporting = [false; 16];
+        self.sampling_interval = Duration::from_millis(DEFAULT_SAMPLING_INTERVAL);
+        self.i2c_reads.clear();
+        self.steppers.clear();
+    }
+
+    fn queue(&mut self, message: &FirmataMessage) {
+        self.output.extend(message.to_bytes());
+    }
+
+    fn supports(&self, pin: u8, mode: PinMode) -> bool {
+        self.profile
+            .pins
+            .get(pin as usize)
+            .is_some_and(|modes| modes.iter().any(|m| m.mode == mode))
+    }
+
+    fn analog_pin(&self, channel: u8) -> Option<usize> {
+        self.profile.analog_channels.iter().position(|c| *c == Some(channel))
+    }
+
+    /// Returns the levels of the input pins of `port`.
+    fn port_value(&self, port: u8) -> u8 {
+        (0..8).fold(0u8, |value, bit| {
+            match self.pins.get(port as usize * 8 + bit) {
+                Some(p) if (p.mode == PinMode::Input || p.mode == PinMode::Pullup)
+                    && p.value != 0 => value | (1 << bit),
+                _ => value,
+            }
+        })
+    }
+
+    fn report_analog_channel(&mut self, channel: u8) {
+        let value = match self.analog_pin(channel).map(|pin| &self.pins[pin]) {
+            Some(p) if p.mode == PinMode::Analog => p.value,
+            _ => return,
+        };
+        self.queue(&FirmataMessage::AnalogMessage { channel, value });
+    }
+
+    fn set_analog_reporting(&mut self, channel: u8, enabled: bool) {
+        if let Some(reporting) = self.analog_reporting.get_mut(channel as usize) {
+            *reporting = enabled;
+        } else {
+            return;
+        }
+        if enabled {
+            self.report_analog_channel(channel);
+        }
+    }
+
+    fn write_analog(&mut self, pin: u8, value: i32) {
+        if let Some(p) = self.pins.get_mut(pin as usize) {
+            if p.mode == PinMode::Pwm || p.mode == PinMode::Servo {
+                p.value = value;
+            }
+        }
+    }
+
+    fn set_pin_mode(&mut self, pin: u8, mode: PinMode) {
+        if !self.supports(pin, mode) {
+            self.send_string("Unknown pin mode");
+            return;
+        }
+
+        let p = &mut self.pins[pin as usize];
+        p.mode = mode;
+        match mode {
+            PinMode::Output | PinMode::Pwm | PinMode::Servo => p.value = 0,
+            // Nothing drives a pulled up input until told otherwise.
+            PinMode::Pullup => p.value = 1,
+            _ => {}
+        }
+
+        // Like StandardFirmata, analog reporting follows the mode of an
+        // analog pin.
+        if let Some(&Some(channel)) = self.profile.analog_channels.get(pin as usize) {
+            self.set_analog_reporting(channel, mode == PinMode::Analog);
+        }
+    }
+
+    fn i2c_read(&mut self, request: &I2CRequest) {
+        let data = match self.i2c_devices.get_mut(&request.address) {
+            Some(device) => {
+                if let Some(register) = request.register {
+                    device.write(&[register as u8]);
+                }
+                device.read(request.size as usize)
+            }
+            None => return,
+        };
+
+        // StandardFirmata reports a read without a register as register 255.
+        self.queue(&FirmataMessage::I2CReply(I2CReply {
+            address: request.address as i32,
+            register: request.register.map_or(0xFF, |r| r as i32),
+            data,
+        }));
+    }
+
+    fn handle(&mut self, command: Command) {
+        match command {
+            Command::AnalogMessage { pin, value } => self.write_analog(pin, value as i32),
+            Command::DigitalMessage { port, value } => {
+                for bit in 0..8 {
+                    if let Some(p) = self.pins.get_mut(port as usize * 8 + bit) {
+                        if p.mode == PinMode::Output {
+                            p.value = ((value >> bit) & 1) as i32;
+                        }
+                    }
+                }
+            }
+            Command::ReportAnalog { channel, enabled } => {
+                self.set_analog_reporting(channel, enabled)
+            }
+            Command::ReportDigital { port, enabled } => {
+                if let Some(reporting) = self.digital_reporting.get_mut(port as usize) {
+                    *reporting = enabled;
+                }
+                if enabled {
+                    let value = self.port_value(port) as i32;
+                    self.queue(&FirmataMessage::DigitalMessage { port, value });
+                }
+            }
+            Command::SetPinMode { pin, mode } => self.set_pin_mode(pin, mode),
+            Command::SetDigitalPinValue { pin, value } => {
+                if let Some(p) = self.pins.get_mut(pin as usize) {
+                    if p.mode == PinMode::Output {
+                        p.value = value as i32;
+                    }
+                }
+            }
+            Command::ReportVersion => {
+                let message = self.profile.protocol_version_message();
+                self.queue(&message);
+            }
+            Command::SystemReset => self.reset(),
+            Command::AnalogMappingQuery => {
+                let message = self.profile.analog_mapping_response();
+                self.queue(&message);
+            }
+            Command::CapabilityQuery => {
+                let message = self.profile.capability_response();
+                self.queue(&message);
+            }
+            Command::PinStateQuery { pin } => {
+                let message = match self.pins.get(pin as usize) {
+                    Some(p) => FirmataMessage::PinStateResponse {
+                        pin,
+                        mode: p.mode,
+                        state: p.value,
+                    },
+                    None => return,
+                };
+                self.queue(&message);
+            }
+            Command::ReportFirmware => {
+                let message = self.profile.report_firmware_message();
+                self.queue(&message);
+            }
+            Command::ExtendedAnalog { pin, value } => self.write_analog(pin, value as i32),
+            Command::ServoConfig { pin, .. } => {
+                if self.supports(pin, PinMode::Servo) {
+                    self.set_pin_mode(pin, PinMode::Servo);
+                }
+            }
+            Command::SamplingInterval { interval } => {
+                self.sampling_interval = Duration::from_millis(interval.max(1) as u64);
+            }
+            Command::StringData(text) => self.strings.push(text),
+            Command::I2CConfig { .. } => {}
+            Command::I2CRequest(request) => match request.mode {
+                I2CMode::Write => {
+                    if let Some(device) = self.i2c_devices.get_mut(&request.address) {
+                        device.write(&request.data);
+                    }
+                }
+                I2CMode::Read => self.i2c_read(&request),
+                I2CMode::ReadContinuously => self.i2c_reads.push(request),
+                I2CMode::StopReading => self.i2c_reads.retain(|r| r.address != request.address),
+            },
+            Command::StepperConfig { device, interface, .. } => {
+                let pins = interface.pins();
+                let supported = pins.iter().all(|pin| self.supports(*pin, PinMode::Stepper));
+                if device < MAX_STEPPERS && supported {
+                    for pin in pins {
+                        self.pins[pin as usize].mode = PinMode::Stepper;
+                    }
+                    self.steppers.insert(device, 0);
+                }
+            }
+            Command::StepperStep(step) => {
+                // Moves complete as soon as they start.
+                if let Some(position) = self.steppers.get_mut(&step.device) {
+                    *position += match step.direction {
+                        StepDirection::Clockwise => step.steps as i64,
+                        StepDirection::CounterClockwise => -(step.steps as i64),
+                    };
+                    self.queue(&FirmataMessage::StepperMoveComplete { device: step.device });
+                }
+            }
+            // Like StandardFirmata, other sysex commands are ignored.
+            Command::Sysex { .. } => {}
+        }
+    }
+
+    /// Handles every complete command in the input buffer.
+    fn process(&mut self) {
+        loop {
+            let start = self
+                .input
+                .iter()
+                .position(|b| b & 0x80 != 0)
+                .unwrap_or(self.input.len());
+            self.input.drain(..start);
+
+            let command = match self.input.first() {
+                Some(command) => *command,
+                None => return,
+            };
+            let (len, body) = match command {
+                START_SYSEX => match self.input.iter().position(|b| *b == END_SYSEX) {
+                    Some(end) => (end + 1, end),
+                    None => (self.input.len() + 1, self.input.len()),
+                },
+                REPORT_ANALOG..=0xDF => (2, self.input.len().min(2)),
+                DIGITAL_MESSAGE..=0x9F | ANALOG_MESSAGE..=0xEF | PIN_MODE
+                    | SET_DIGITAL_PIN_VALUE => {
+                    (3, self.input.len().min(3))
+                }
+                _ => (1, 1),
+            };
+
+            // A frame interrupted by another command byte is dropped, like
+            // the firmware does.
+            if let Some(i) = self.input[1..body].iter().position(|b| b & 0x80 != 0) {
+                self.input.drain(..i + 1);
+                continue;
+            }
+            if self.input.len() < len {
+                return;
+            }
+
+            let frame: Vec<u8> = self.input.drain(..len).collect();
+            if let Some(command) = parse_command(&frame) {
+                self.handle(command);
+            }
+        }
+    }
+}
+
+impl io::Read for Simulator {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.output.is_empty() && self.last_sample.elapsed() >= self.sampling_interval {
+            self.sample();
+        }
+        if self.output.is_empty() {
+            thread::sleep(Duration::from_millis(1));
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "nothing to report"));
+        }
+
+        let n = buf.len().min(self.output.len());
+        for (b, out) in buf.iter_mut().zip(self.output.drain(..n)) {
+            *b = out;
+        }
+        Ok(n)
+    }
+}
+
+impl io::Write for Simulator {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.input.extend_from_slice(buf);
+        self.process();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn u14(lsb: u8, msb: u8) -> u16 {
+    (lsb as u16) | ((msb as u16) << 7)
+}
+
+/// Parses a complete frame sent by the board. Frames the simulated firmware
+/// does not understand are `None`.
+fn parse_command(frame: &[u8]) -> Option<Command> {
+    let command = frame[0];
+    let parsed = match command {
+        ANALOG_MESSAGE..=0xEF => Command::AnalogMessage {
+            pin: command & 0x0F,
+            value: u14(frame[1], frame[2]),
+        },
+        DIGITAL_MESSAGE..=0x9F => Command::DigitalMessage {
+            port: command & 0x0F,
+            value: u14(frame[1], frame[2]) as u8,
+        },
+        REPORT_ANALOG..=0xCF => Command::ReportAnalog {
+            channel: command & 0x0F,
+            enabled: frame[1] != 0,
+        },
+        REPORT_DIGITAL..=0xDF => Command::ReportDigital {
+            port: command & 0x0F,
+            enabled: frame[1] != 0,
+        },
+        PIN_MODE => Command::SetPinMode {
+            pin: frame[1],
+            mode: PinMode::from(frame[2]),
+        },
+        SET_DIGITAL_PIN_VALUE => Command::SetDigitalPinValue {
+            pin: frame[1],
+            value: frame[2] != 0,
+        },
+        PROTOCOL_VERSION => Command::ReportVersion,
+        SYSTEM_RESET => Command::SystemReset,
+        START_SYSEX if frame.len() >= 3 => parse_sysex(frame[1], &frame[2..frame.len() - 1])?,
+        _ => return None,
+    };
+    Some(parsed)
+}
+
+fn parse_sysex(id: u8, data: &[u8]) -> Option<Command> {
+    let command = match id {
+        ANALOG_MAPPING_QUERY => Command::AnalogMappingQuery,
+        CAPABILITY_QUERY => Command::CapabilityQuery,
+        PIN_STATE_QUERY => Command::PinStateQuery { pin: *data.first()? },
+        REPORT_FIRMWARE => Command::ReportFirmware,
+        EXTENDED_ANALOG => Command::ExtendedAnalog {
+            pin: *data.first()?,
+            value: data[1..]
+                .iter()
+                .take(5)
+                .enumerate()
+                .fold(0u32, |value, (i, b)| value | ((*b as u32) << (7 * i))),
+        },
+        SERVO_CONFIG if data.len() >= 5 => Command::ServoConfig {
+            pin: data[0],
+            min_pulse: u14(data[1], data[2]),
+            max_pulse: u14(data[3], data[4]),
+        },
+        SAMPLING_INTERVAL if data.len() >= 2 => Command::SamplingInterval {
+            interval: u14(data[0], data[1]),
+        },
+        STRING_DATA => Command::StringData(String::from_utf8(decode_bytes(data)).ok()?),
+        I2C_CONFIG => Command::I2CConfig {
+            delay: if data.len() >= 2 { u14(data[0], data[1]) } else { 0 },
+        },
+        I2C_REQUEST if data.len() >= 2 => Command::I2CRequest(parse_i2c_request(data)),
+        STEPPER_DATA => parse_stepper(data)?,
+        _ => return None,
+    };
+    Some(command)
+}
+
+fn parse_i2c_request(data: &[u8]) -> I2CRequest {
+    let flags = data[1];
+    let ten_bit_address = flags & I2C_10BIT_ADDRESS != 0;
+    let address = if ten_bit_address {
+        data[0] as u16 | ((flags as u16 & 0x07) << 7)
+    } else {
+        data[0] as u16
+    };
+    let mode = match (flags >> 3) & 0x03 {
+        0 => I2CMode::Write,
+        1 => I2CMode::Read,
+        2 => I2CMode::ReadContinuously,
+        _ => I2CMode::StopReading,
+    };
+    let values: Vec<u16> = data[2..]
+        .chunks(2)
+        .filter(|pair| pair.len() == 2)
+        .map(|pair| u14(pair[0], pair[1]))
+        .collect();
+
+    let mut request = match mode {
+        I2CMode::Write => I2CRequest::write(address, &decode_bytes(&data[2..])),
+        I2CMode::Read | I2CMode::ReadContinuously => {
+            // The register is optional and comes before the size.
+            let (register, size) = match values.len() {
+                0 => (None, 0),
+                1 => (None, values[0]),
+                _ => (Some(values[0]), values[1]),
+            };
+            let mut request = if mode == I2CMode::Read {
+                I2CRequest::read(address, size)
+            } else {
+                I2CRequest::read_continuously(address, size)
+            };
+            request.register = register;
+            request
+        }
+        I2CMode::StopReading => I2CRequest::stop_reading(address),
+    };
+    request.ten_bit_address = ten_bit_address;
+    request.auto_restart = flags & I2C_AUTO_RESTART != 0;
+    request
+}
+
+fn parse_stepper(data: &[u8]) -> Option<Command> {
+    match *data.first()? {
+        STEPPER_CONFIG if data.len() >= 7 => {
+            let pins = &data[5..];
+            let interface = match data[2] {
+                0x01 => StepperInterface::Driver { direction_pin: pins[0], step_pin: pins[1] },
+                0x02 => StepperInterface::TwoWire { motor_pins: [pins[0], pins[1]] },
+                0x04 if pins.len() >= 4 => StepperInterface::FourWire {
+                    motor_pins: [pins[0], pins[1], pins[2], pins[3]],
+                },
+                _ => return None,
+            };
+            Some(Command::StepperConfig {
+                device: data[1],
+                interface,
+                steps_per_revolution: u14(data[3], data[4]),
+            })
+        }
+        STEPPER_STEP if data.len() >= 8 => {
+            let direction = if data[2] == 0 {
+                StepDirection::Clockwise
+            } else {
+                StepDirection::CounterClockwise
+            };
+            let steps = data[3] as u32 | (data[4] as u32) << 7 | (data[5] as u32) << 14;
+            let mut step = StepperMove::new(data[1], direction, steps, u14(data[6], data[7]));
+            if data.len() >= 12 {
+                step = step.ramp(u14(data[8], data[9]), u14(data[10], data[11]));
+            }
+            Some(Command::StepperStep(step))
+        }
+        _ => None,
+    }
+}
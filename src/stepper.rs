@@ -0,0 +1,114 @@
+//! This module contains the typed options of the legacy stepper commands.
+use command::push_u14;
+use {Error, Result, MAX_STEPPERS};
+
+/// How a stepper motor is wired to the board.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepperInterface {
+    /// A step and direction driver such as the EasyDriver or A4988.
+    Driver { step_pin: u8, direction_pin: u8 },
+    /// A motor driven through two pins.
+    TwoWire { motor_pins: [u8; 2] },
+    /// A motor driven through four pins.
+    FourWire { motor_pins: [u8; 4] },
+}
+
+impl StepperInterface {
+    /// Returns the pins used by the interface, in wire order.
+    pub fn pins(&self) -> Vec<u8> {
+        match *self {
+            StepperInterface::Driver { step_pin, direction_pin } => vec![direction_pin, step_pin],
+            StepperInterface::TwoWire { motor_pins } => motor_pins.to_vec(),
+            StepperInterface::FourWire { motor_pins } => motor_pins.to_vec(),
+        }
+    }
+
+    pub(crate) fn bits(&self) -> u8 {
+        match *self {
+            StepperInterface::Driver { .. } => 0x01,
+            StepperInterface::TwoWire { .. } => 0x02,
+            StepperInterface::FourWire { .. } => 0x04,
+        }
+    }
+}
+
+/// The direction a stepper motor turns in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepDirection {
+    Clockwise,
+    CounterClockwise,
+}
+
+/// A move of a stepper motor.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StepperMove {
+    /// The stepper device (0-5) to move.
+    pub device: u8,
+    pub direction: StepDirection,
+    /// The number of steps to take, up to 21 bits.
+    pub steps: u32,
+    /// The speed in 0.01 rad/s.
+    pub speed: u16,
+    /// The acceleration in 0.01 rad/s², if the move ramps up.
+    pub acceleration: Option<u16>,
+    /// The deceleration in 0.01 rad/s², if the move ramps down.
+    pub deceleration: Option<u16>,
+}
+
+impl StepperMove {
+    /// Creates a move of `steps` steps at a constant `speed`.
+    pub fn new(device: u8, direction: StepDirection, steps: u32, speed: u16) -> StepperMove {
+        StepperMove {
+            device,
+            direction,
+            steps,
+            speed,
+            acceleration: None,
+            deceleration: None,
+        }
+    }
+
+    /// Ramps the speed up with `acceleration` and down with `deceleration`.
+    pub fn ramp(mut self, acceleration: u16, deceleration: u16) -> StepperMove {
+        self.acceleration = Some(acceleration);
+        self.deceleration = Some(deceleration);
+        self
+    }
+
+    /// Checks that the device and every value fit in their wire encoding.
+    pub fn validate(&self) -> Result<()> {
+        if self.device >= MAX_STEPPERS {
+            return Err(Error::OutOfRange("stepper device"));
+        }
+        if self.steps > 0x1F_FFFF {
+            return Err(Error::OutOfRange("stepper steps"));
+        }
+        let ramp = self.acceleration.iter().chain(self.deceleration.iter());
+        if self.speed > 0x3FFF || ramp.cloned().any(|v| v > 0x3FFF) {
+            return Err(Error::OutOfRange("stepper speed"));
+        }
+        Ok(())
+    }
+
+    /// Appends the body of the step command, after its subcommand byte, to
+    /// `buf`.
+    pub(crate) fn encode(&self, buf: &mut Vec<u8>) {
+        let direction = match self.direction {
+            StepDirection::Clockwise => 0x00,
+            StepDirection::CounterClockwise => 0x01,
+        };
+        buf.extend_from_slice(&[
+            self.device & 0x7F,
+            direction,
+            (self.steps & 0x7F) as u8,
+            ((self.steps >> 7) & 0x7F) as u8,
+            ((self.steps >> 14) & 0x7F) as u8,
+        ]);
+        push_u14(buf, self.speed);
+        // Acceleration and deceleration are sent together or not at all.
+        if self.acceleration.is_some() || self.deceleration.is_some() {
+            push_u14(buf, self.acceleration.unwrap_or(0));
+            push_u14(buf, self.deceleration.unwrap_or(0));
+        }
+    }
+}
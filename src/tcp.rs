@@ -0,0 +1,28 @@
+//! This module contains helpers for talking to network-attached boards, such
+//! as ESP8266 or ESP32 boards running WiFi Firmata.
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use {Board, BoardBuilder, Result};
+
+/// How long a single read blocks before the board checks its query
+/// deadlines again.
+const READ_TIMEOUT: Duration = Duration::from_millis(100);
+
+impl Board<TcpStream> {
+    /// Connects to the board listening at `addr` and runs the default
+    /// handshake of `BoardBuilder`.
+    pub fn connect_tcp<A: ToSocketAddrs>(addr: A) -> Result<Board<TcpStream>> {
+        BoardBuilder::new().build(Box::new(open_tcp_stream(addr)?))
+    }
+}
+
+/// Connects to `addr` and configures the stream for firmata: reads time out
+/// so queries can give up, and Nagle's algorithm is disabled so short
+/// commands are sent immediately.
+pub fn open_tcp_stream<A: ToSocketAddrs>(addr: A) -> Result<TcpStream> {
+    let stream = TcpStream::connect(addr)?;
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    stream.set_nodelay(true)?;
+    Ok(stream)
+}
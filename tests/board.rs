@@ -0,0 +1,424 @@
+extern crate firmata;
+
+use firmata::*;
+use std::io::{ErrorKind, Read, Write};
+use std::net::TcpListener;
+use std::thread;
+use std::time::Duration;
+
+fn uno() -> Board<MockConnection> {
+    let mut board = Board::new(Box::new(MockConnection::uno())).unwrap();
+    board.connection.take_written();
+    board
+}
+
+#[test]
+fn handshake_reads_profile() {
+    let board = Board::new(Box::new(MockConnection::uno())).unwrap();
+
+    assert_eq!(board.firmware_name, "StandardFirmata.ino");
+    assert_eq!(board.firmware_version, "2.5");
+    assert_eq!(board.protocol_version, "2.5");
+    assert_eq!(board.pins.len(), 20);
+    assert_eq!(board.pins[14].analog_channel, Some(0));
+    assert_eq!(board.pins[13].analog_channel, None);
+    assert_eq!(
+        board.connection.written,
+        vec![0xF9, 0xF0, 0x79, 0xF7, 0xF0, 0x6B, 0xF7, 0xF0, 0x69, 0xF7, 0xD0, 0x01, 0xD1, 0x01]
+    );
+}
+
+#[test]
+fn versions_are_printed_in_decimal() {
+    let mut profile = Profile::uno();
+    profile.protocol_version = (2, 10);
+    profile.firmware_version = (2, 12);
+    let board = Board::new(Box::new(MockConnection::with_profile(&profile))).unwrap();
+
+    assert_eq!(board.protocol_version, "2.10");
+    assert_eq!(board.firmware_version, "2.12");
+}
+
+#[test]
+fn handshake_skips_unrelated_reports() {
+    let mut connection = MockConnection::new();
+    connection.respond_bytes(&[0x00, 0x42, 0x91]);
+    connection.respond(&FirmataMessage::AnalogMessage { channel: 0, value: 512 });
+    for message in Profile::mega().handshake() {
+        connection.respond(&message);
+    }
+
+    let board = Board::new(Box::new(connection)).unwrap();
+
+    assert_eq!(board.pins.len(), 70);
+    assert_eq!(board.pins[54].analog_channel, Some(0));
+    assert!(board.pins[0].modes.is_empty());
+    assert!(board.pins[14].modes.iter().any(|m| m.mode == PinMode::Serial));
+}
+
+#[test]
+fn profiles_limit_servos_to_max_servos() {
+    let servo = |modes: &Vec<Mode>| modes.iter().any(|m| m.mode == PinMode::Servo);
+    let uno = Profile::uno();
+    let mega = Profile::mega();
+
+    assert!(servo(&uno.pins[2]) && servo(&uno.pins[13]));
+    assert!(!servo(&uno.pins[1]) && !servo(&uno.pins[14]));
+    assert!(servo(&mega.pins[2]) && servo(&mega.pins[49]));
+    assert!(!servo(&mega.pins[50]) && !servo(&mega.pins[69]));
+}
+
+#[test]
+fn analog_reports_follow_mapping() {
+    let mut board = Board::new(Box::new(MockConnection::mega())).unwrap();
+    board.connection.respond(&FirmataMessage::AnalogMessage { channel: 2, value: 300 });
+
+    board.read_and_decode().unwrap();
+
+    assert_eq!(board.pins[56].reported_value, 300);
+    assert_eq!(board.pins[16].reported_value, 0);
+    assert_eq!(board.analog_channel(56), Some(2));
+    assert_eq!(board.analog_channel(13), None);
+    assert_eq!(board.analog_pin(2), Some(56));
+    assert_eq!(board.analog_pin(16), None);
+}
+
+#[test]
+fn handshake_times_out_without_answer() {
+    let result = BoardBuilder::new()
+        .timeout(Duration::from_millis(10))
+        .build(Box::new(MockConnection::new()));
+
+    match result {
+        Err(Error::Timeout) => {},
+        other => panic!("expected a timeout, got {:?}", other.err()),
+    }
+}
+
+#[test]
+fn digital_write_sets_single_pin() {
+    let mut board = uno();
+
+    board.set_pin_mode(13, PinMode::Output).unwrap();
+    board.digital_write(13, 1).unwrap();
+    board.set_digital_pin_value(13, 0).unwrap();
+    assert_eq!(board.pins[13].commanded_value, 0);
+    assert!(board.pins[13].commanded_at.is_some());
+    assert!(board.pins[13].reported_at.is_none());
+
+    assert_eq!(board.connection.written, vec![0xF4, 13, 1, 0xF5, 13, 1, 0xF5, 13, 0]);
+}
+
+#[test]
+fn digital_write_composes_port_before_protocol_2_5() {
+    let mut profile = Profile::uno();
+    profile.protocol_version = (2, 3);
+    let mut board = Board::new(Box::new(MockConnection::with_profile(&profile))).unwrap();
+    board.connection.take_written();
+    // Pin 9 is an input that reads high; it must not leak into the port.
+    board.connection.respond(&FirmataMessage::DigitalMessage { port: 1, value: 0x02 });
+    board.read_and_decode().unwrap();
+    assert_eq!(board.pins[9].reported_value, 1);
+
+    board.set_pin_mode(13, PinMode::Output).unwrap();
+    board.set_pin_mode(8, PinMode::Output).unwrap();
+    board.digital_write(8, 1).unwrap();
+    board.digital_write(13, 1).unwrap();
+
+    assert_eq!(
+        board.connection.written,
+        vec![0xF4, 13, 1, 0xF4, 8, 1, 0x91, 0x01, 0x00, 0x91, 0x21, 0x00]
+    );
+    match board.set_digital_pin_value(13, 0) {
+        Err(Error::UnsupportedProtocol { required: (2, 5), reported: (2, 3) }) => {},
+        other => panic!("expected an unsupported protocol, got {:?}", other),
+    }
+}
+
+#[test]
+fn pullup_inputs_see_reports() {
+    let mut board = uno();
+    board.set_pin_mode(4, PinMode::Pullup).unwrap();
+    board.connection.respond(&FirmataMessage::DigitalMessage { port: 0, value: 0x10 });
+
+    board.read_and_decode().unwrap();
+
+    assert_eq!(board.pins[4].reported_value, 1);
+    assert_eq!(board.connection.written, vec![0xF4, 4, 0x0B]);
+}
+
+#[test]
+fn pin_validation() {
+    let mut board = uno();
+
+    match board.digital_write(20, 1) {
+        Err(Error::InvalidPin(20)) => {},
+        other => panic!("expected an invalid pin, got {:?}", other),
+    }
+    match board.set_pin_mode(13, PinMode::Analog) {
+        Err(Error::UnsupportedMode { pin: 13, mode: PinMode::Analog }) => {},
+        other => panic!("expected an unsupported mode, got {:?}", other),
+    }
+    assert!(board.connection.written.is_empty());
+}
+
+#[test]
+fn builder_reports_pins() {
+    let board = BoardBuilder::new()
+        .report_digital(&[])
+        .report_pins(&[2, 15])
+        .build(Box::new(MockConnection::uno()))
+        .unwrap();
+
+    assert!(board.connection.written.ends_with(&[0xD0, 0x01, 0xC1, 0x01]));
+}
+
+#[test]
+fn reporting_is_counted_per_pin() {
+    let mut board = BoardBuilder::new()
+        .report_digital(&[])
+        .build(Box::new(MockConnection::uno()))
+        .unwrap();
+    board.connection.take_written();
+
+    board.enable_reporting(2).unwrap();
+    board.enable_reporting(3).unwrap();
+    board.disable_reporting(2).unwrap();
+    assert_eq!(board.reporting_ports(), vec![0]);
+    board.disable_reporting(3).unwrap();
+    assert_eq!(board.connection.take_written(), vec![0xD0, 0x01, 0xD0, 0x00]);
+    assert!(board.reporting_ports().is_empty());
+
+    // The firmware starts reporting a channel when its pin enters analog
+    // mode, so enabling it again sends nothing.
+    board.set_pin_mode(15, PinMode::Analog).unwrap();
+    board.connection.take_written();
+    assert_eq!(board.reporting_channels(), vec![1]);
+    board.enable_reporting(15).unwrap();
+    board.report_digital(1, 1).unwrap();
+    assert_eq!(board.connection.take_written(), vec![0xD1, 0x01]);
+    assert_eq!(board.reporting_ports(), vec![1]);
+    board.disable_reporting(15).unwrap();
+    assert_eq!(board.connection.take_written(), vec![0xC1, 0x00]);
+
+    board.set_pin_mode(14, PinMode::Analog).unwrap();
+    board.set_pin_mode(14, PinMode::Input).unwrap();
+    assert!(board.reporting_channels().is_empty());
+
+    match board.enable_reporting(0) {
+        Err(Error::UnsupportedMode { pin: 0, .. }) => {},
+        other => panic!("expected an unsupported mode, got {:?}", other),
+    }
+}
+
+#[test]
+fn analog_write_falls_back_to_extended_analog() {
+    let mut board = Board::new(Box::new(MockConnection::mega())).unwrap();
+    board.connection.take_written();
+
+    board.analog_write(44, 200).unwrap();
+    board.analog_write(3, 0x4000).unwrap();
+    board.analog_write(3, 200).unwrap();
+
+    assert_eq!(
+        board.connection.written,
+        vec![
+            0xF0, 0x6F, 44, 0x48, 0x01, 0xF7,
+            0xF0, 0x6F, 3, 0x00, 0x00, 0x01, 0xF7,
+            0xE3, 0x48, 0x01,
+        ]
+    );
+}
+
+#[test]
+fn analog_write_rejects_unencodable_values() {
+    let mut profile = Profile::mega();
+    profile.pins.resize(130, vec![]);
+    let mut board = Board::new(Box::new(MockConnection::with_profile(&profile))).unwrap();
+    board.connection.take_written();
+
+    match board.analog_write(3, -1) {
+        Err(Error::OutOfRange(_)) => {},
+        other => panic!("expected an out of range level, got {:?}", other),
+    }
+    match board.analog_write(128, 1) {
+        Err(Error::OutOfRange(_)) => {},
+        other => panic!("expected an out of range pin, got {:?}", other),
+    }
+    assert!(board.connection.written.is_empty());
+    assert!(board.pins[3].commanded_at.is_none());
+}
+
+#[test]
+fn read_and_decode_applies_pin_state() {
+    let mut board = uno();
+    board.connection.respond(&FirmataMessage::PinStateResponse {
+        pin: 9,
+        mode: PinMode::Pwm,
+        state: 200,
+    });
+
+    let message = board.read_and_decode().unwrap();
+
+    assert_eq!(message, FirmataMessage::PinStateResponse { pin: 9, mode: PinMode::Pwm, state: 200 });
+    assert_eq!(board.pins[9].mode, PinMode::Pwm);
+    assert_eq!(board.pins[9].reported_value, 200);
+    assert_eq!(board.pins[9].resolution, 8);
+}
+
+#[test]
+fn stepper_moves_wait_for_completion() {
+    let mut profile = Profile::uno();
+    for modes in profile.pins.iter_mut().skip(2) {
+        modes.push(Mode { mode: PinMode::Stepper, resolution: 1 });
+    }
+    let mut board = Board::new(Box::new(MockConnection::with_profile(&profile))).unwrap();
+    board.connection.take_written();
+
+    let interface = StepperInterface::Driver { step_pin: 2, direction_pin: 3 };
+    board.stepper_config(0, interface, 200).unwrap();
+    board.stepper_step(&StepperMove::new(0, StepDirection::Clockwise, 10, 100)).unwrap();
+    board.connection.respond(&FirmataMessage::StepperMoveComplete { device: 1 });
+    board.connection.respond(&FirmataMessage::StepperMoveComplete { device: 0 });
+
+    board.stepper_wait(0, Duration::from_millis(10)).unwrap();
+
+    assert_eq!(board.completed_moves, vec![1]);
+    assert_eq!(board.pins[2].mode, PinMode::Stepper);
+    match board.stepper_wait(0, Duration::from_millis(10)) {
+        Err(Error::Timeout) => {},
+        other => panic!("expected a timeout, got {:?}", other),
+    }
+    match board.stepper_config(6, interface, 200) {
+        Err(Error::OutOfRange(_)) => {},
+        other => panic!("expected an out of range device, got {:?}", other),
+    }
+    match uno().stepper_config(0, interface, 200) {
+        Err(Error::UnsupportedMode { pin: 3, mode: PinMode::Stepper }) => {},
+        other => panic!("expected an unsupported mode, got {:?}", other),
+    }
+}
+
+#[test]
+fn sampling_interval_has_a_1ms_minimum() {
+    let mut board = uno();
+
+    board.set_sampling_interval(Duration::from_micros(500)).unwrap();
+
+    assert_eq!(board.sampling_interval, Duration::from_millis(1));
+    assert_eq!(board.connection.written, vec![0xF0, 0x7A, 0x01, 0x00, 0xF7]);
+}
+
+#[test]
+fn read_and_decode_times_out_on_quiet_connection() {
+    let mut board = uno();
+    board.set_timeout(Duration::from_millis(20));
+
+    match board.read_and_decode() {
+        Err(Error::Timeout) => {},
+        other => panic!("expected a timeout, got {:?}", other),
+    }
+}
+
+#[test]
+fn read_and_decode_reports_unknown_sysex() {
+    let mut board = uno();
+    board.connection.respond_bytes(&[0xF0, 0x01, 0x02, 0x03, 0xF7]);
+
+    match board.read_and_decode() {
+        Err(Error::UnknownSysex { id: 0x01, payload }) => assert_eq!(payload, vec![0x02, 0x03]),
+        other => panic!("expected an unknown sysex, got {:?}", other),
+    }
+}
+
+#[test]
+fn i2c_config_checks_delay() {
+    let mut board = uno();
+
+    board.i2c_config(0x3FFF).unwrap();
+    for delay in &[70000, -1] {
+        match board.i2c_config(*delay) {
+            Err(Error::OutOfRange(_)) => {},
+            other => panic!("expected an out of range delay, got {:?}", other),
+        }
+    }
+    assert_eq!(board.connection.written, vec![0xF0, 0x78, 0x7F, 0x7F, 0xF7]);
+}
+
+#[test]
+fn i2c_read_reply_waits_for_matching_reply() {
+    let mut board = uno();
+    board.connection.respond(&FirmataMessage::I2CReply(I2CReply {
+        address: 0x10,
+        register: 0x00,
+        data: vec![0xFF],
+    }));
+    board.connection.respond(&FirmataMessage::I2CReply(I2CReply {
+        address: 0x09,
+        register: 0x02,
+        data: vec![0xAB, 0x80],
+    }));
+
+    let reply = board.i2c_read_reply(0x09, Some(0x02), 2, Duration::from_millis(10)).unwrap();
+
+    assert_eq!(reply.data, vec![0xAB, 0x80]);
+    assert_eq!(board.i2c_data()[&(0x10, 0x00)].len(), 1);
+    assert_eq!(board.connection.written, vec![0xF0, 0x76, 0x09, 0x08, 0x02, 0x00, 0x02, 0x00, 0xF7]);
+}
+
+#[test]
+fn i2c_read_reply_skips_stale_replies() {
+    let mut board = uno();
+    let stale = I2CReply { address: 0x09, register: 0x02, data: vec![0x01] };
+    board.connection.respond(&FirmataMessage::I2CReply(stale.clone()));
+    board.read_and_decode().unwrap();
+    board.connection.respond(&FirmataMessage::I2CReply(I2CReply {
+        address: 0x09,
+        register: 0x02,
+        data: vec![0x02, 0x03],
+    }));
+
+    let reply = board.i2c_read_reply(0x09, Some(0x02), 2, Duration::from_millis(10)).unwrap();
+
+    assert_eq!(reply.data, vec![0x02, 0x03]);
+    assert_eq!(board.i2c_data()[&(0x09, 0x02)], vec![stale]);
+    assert_eq!(board.connection.written, vec![0xF0, 0x76, 0x09, 0x08, 0x02, 0x00, 0x02, 0x00, 0xF7]);
+}
+
+#[test]
+fn connect_tcp_runs_handshake() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    // Relays between the socket and a simulated Uno until the board hangs up.
+    let server = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        stream.set_read_timeout(Some(Duration::from_millis(5))).unwrap();
+        let mut simulator = Simulator::uno();
+        let mut buf = [0u8; 256];
+        loop {
+            match stream.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => simulator.write_all(&buf[..n]).unwrap(),
+                Err(ref e) if e.kind() == ErrorKind::WouldBlock
+                    || e.kind() == ErrorKind::TimedOut => {},
+                Err(_) => break,
+            }
+            while let Ok(n) = simulator.read(&mut buf) {
+                stream.write_all(&buf[..n]).unwrap();
+            }
+        }
+        simulator.pins().to_vec()
+    });
+
+    let mut board = Board::connect_tcp(addr).unwrap();
+    assert_eq!(board.firmware_name, "StandardFirmata.ino");
+    assert_eq!(board.pins.len(), 20);
+    board.set_pin_mode(13, PinMode::Output).unwrap();
+    board.digital_write(13, 1).unwrap();
+    board.query_pin_state(13).unwrap();
+    assert_eq!(board.pins[13].reported_value, 1);
+    drop(board);
+
+    let pins = server.join().unwrap();
+    assert_eq!(pins[13], SimulatedPin { mode: PinMode::Output, value: 1 });
+}
@@ -0,0 +1,139 @@
+extern crate firmata;
+
+use firmata::*;
+
+#[test]
+fn decoder_handles_split_frames() {
+    let mut decoder = Decoder::new();
+    let bytes = FirmataMessage::StringData("hello".to_string()).to_bytes();
+
+    decoder.push(&bytes[..3]);
+    assert_eq!(decoder.decode().unwrap(), None);
+    decoder.push(&bytes[3..]);
+
+    assert_eq!(decoder.decode().unwrap(), Some(FirmataMessage::StringData("hello".to_string())));
+    assert_eq!(decoder.pending(), 0);
+}
+
+#[test]
+fn decoder_resynchronises_after_garbage() {
+    let mut decoder = Decoder::new();
+    decoder.push(&[0x01, 0x7F, 0xE1, 0x10, 0xF0, 0x79, 0x02, 0x90, 0x03, 0x00]);
+
+    match decoder.decode() {
+        Err(Error::Framing(_)) => {},
+        other => panic!("expected a framing error, got {:?}", other),
+    }
+    match decoder.decode() {
+        Err(Error::Framing(_)) => {},
+        other => panic!("expected a framing error, got {:?}", other),
+    }
+    assert_eq!(decoder.decode().unwrap(), Some(FirmataMessage::DigitalMessage { port: 0, value: 3 }));
+}
+
+#[test]
+fn messages_round_trip() {
+    let messages = vec![
+        FirmataMessage::ProtocolVersion { major: 2, minor: 5 },
+        FirmataMessage::AnalogMessage { channel: 3, value: 1023 },
+        FirmataMessage::PinStateResponse { pin: 3, mode: PinMode::Pwm, state: 0x1FFFF },
+        FirmataMessage::I2CReply(I2CReply { address: 0x09, register: 0x7F, data: vec![0, 128, 255] }),
+        Profile::uno().capability_response(),
+        Profile::mega().analog_mapping_response(),
+        Profile::mega().report_firmware_message(),
+        FirmataMessage::StepperMoveComplete { device: 2 },
+    ];
+    let mut decoder = Decoder::new();
+    for message in &messages {
+        decoder.push(&message.to_bytes());
+    }
+
+    for message in messages {
+        assert_eq!(decoder.decode().unwrap(), Some(message));
+    }
+}
+
+#[test]
+fn encode_i2c_requests() {
+    let read = I2CRequest::read_continuously(0x1E, 6).register(0x03).auto_restart();
+    let stop = I2CRequest::stop_reading(0x1E);
+    let write = I2CRequest::write(0x2A5, &[0xFF]).ten_bit_address();
+
+    assert_eq!(
+        Command::I2CRequest(read).to_bytes(),
+        vec![0xF0, 0x76, 0x1E, 0x50, 0x03, 0x00, 0x06, 0x00, 0xF7]
+    );
+    assert_eq!(Command::I2CRequest(stop).to_bytes(), vec![0xF0, 0x76, 0x1E, 0x18, 0xF7]);
+    assert_eq!(
+        Command::I2CRequest(write).to_bytes(),
+        vec![0xF0, 0x76, 0x25, 0x25, 0x7F, 0x01, 0xF7]
+    );
+}
+
+#[test]
+fn i2c_write_register_is_one_byte() {
+    let write = I2CRequest::write(0x50, &[0x01]).register(0xA0);
+    assert_eq!(
+        Command::I2CRequest(write.clone()).to_bytes(),
+        vec![0xF0, 0x76, 0x50, 0x00, 0x20, 0x01, 0x01, 0x00, 0xF7]
+    );
+    assert!(write.validate().is_ok());
+
+    let write = I2CRequest::write(0x50, &[0x01]).register(0x123);
+    assert!(write.validate().is_err());
+    assert!(I2CRequest::read(0x50, 1).register(0x123).validate().is_ok());
+}
+
+#[test]
+fn encode_stepper_commands() {
+    let config = Command::StepperConfig {
+        device: 0,
+        interface: StepperInterface::FourWire { motor_pins: [8, 9, 10, 11] },
+        steps_per_revolution: 200,
+    };
+    let driver = Command::StepperConfig {
+        device: 1,
+        interface: StepperInterface::Driver { step_pin: 2, direction_pin: 3 },
+        steps_per_revolution: 200,
+    };
+    let step = StepperMove::new(0, StepDirection::CounterClockwise, 1000, 2050).ramp(100, 200);
+
+    assert_eq!(
+        config.to_bytes(),
+        vec![0xF0, 0x72, 0x00, 0x00, 0x04, 0x48, 0x01, 8, 9, 10, 11, 0xF7]
+    );
+    assert_eq!(driver.to_bytes(), vec![0xF0, 0x72, 0x00, 0x01, 0x01, 0x48, 0x01, 3, 2, 0xF7]);
+    assert_eq!(
+        Command::StepperStep(step).to_bytes(),
+        vec![0xF0, 0x72, 0x01, 0x00, 0x01, 0x68, 0x07, 0x00, 0x02, 0x10, 0x64, 0x00, 0x48, 0x01, 0xF7]
+    );
+}
+
+#[test]
+fn encode_core_commands() {
+    let mut buf = vec![];
+    Command::ReportAnalog { channel: 2, enabled: true }.encode(&mut buf);
+    Command::SetPinMode { pin: 5, mode: PinMode::Pullup }.encode(&mut buf);
+    Command::SetDigitalPinValue { pin: 5, value: true }.encode(&mut buf);
+    Command::ServoConfig { pin: 9, min_pulse: 544, max_pulse: 2400 }.encode(&mut buf);
+    Command::SamplingInterval { interval: 100 }.encode(&mut buf);
+
+    assert_eq!(
+        buf,
+        vec![
+            0xC2, 0x01,
+            0xF4, 0x05, 0x0B,
+            0xF5, 0x05, 0x01,
+            0xF0, 0x70, 0x09, 0x20, 0x04, 0x60, 0x12, 0xF7,
+            0xF0, 0x7A, 0x64, 0x00, 0xF7,
+        ]
+    );
+}
+
+#[test]
+fn encode_raw_sysex() {
+    let search = Command::Sysex { id: ONEWIRE_DATA, data: vec![0x40, 0x0A] };
+    assert_eq!(search.to_bytes(), vec![0xF0, 0x73, 0x40, 0x0A, 0xF7]);
+    let reset = Command::Sysex { id: SCHEDULER_DATA, data: vec![0x07] };
+    assert_eq!(reset.to_bytes(), vec![0xF0, 0x7B, 0x07, 0xF7]);
+}
@@ -0,0 +1,156 @@
+extern crate firmata;
+
+use firmata::*;
+use std::time::Duration;
+
+fn uno() -> Board<Simulator> {
+    Board::new(Box::new(Simulator::uno())).unwrap()
+}
+
+/// Reads messages until one matches `done`, failing after a few dozen.
+fn read_until<F: Fn(&FirmataMessage) -> bool>(board: &mut Board<Simulator>, done: F) -> FirmataMessage {
+    for _ in 0..50 {
+        match board.read_and_decode() {
+            Ok(ref message) if done(message) => return message.clone(),
+            _ => {}
+        }
+    }
+    panic!("no matching message");
+}
+
+#[test]
+fn handshake_against_simulator() {
+    let board = Board::new(Box::new(Simulator::mega())).unwrap();
+
+    assert_eq!(board.firmware_name, "StandardFirmata.ino");
+    assert_eq!(board.protocol_version, "2.5");
+    assert_eq!(board.pins.len(), 70);
+    assert_eq!(board.pins[54].analog_channel, Some(0));
+    assert!(board.connection.is_reporting_digital(0));
+    assert!(board.connection.is_reporting_digital(1));
+}
+
+#[test]
+fn outputs_reach_simulator() {
+    let mut board = uno();
+    board.set_pin_mode(13, PinMode::Output).unwrap();
+    board.digital_write(13, 1).unwrap();
+    board.set_pin_mode(9, PinMode::Pwm).unwrap();
+    board.analog_write(9, 128).unwrap();
+
+    assert_eq!(board.connection.pins()[13], SimulatedPin { mode: PinMode::Output, value: 1 });
+    assert_eq!(board.connection.pins()[9], SimulatedPin { mode: PinMode::Pwm, value: 128 });
+
+    board.query_pin_state(9).unwrap();
+    assert_eq!(board.pins[9].reported_value, 128);
+}
+
+#[test]
+fn inputs_are_reported() {
+    let mut board = uno();
+    board.set_pin_mode(2, PinMode::Input).unwrap();
+    board.connection.set_digital_input(2, true);
+
+    let message = read_until(&mut board, |m| match *m {
+        FirmataMessage::DigitalMessage { value, .. } => value != 0,
+        _ => false,
+    });
+    assert_eq!(message, FirmataMessage::DigitalMessage { port: 0, value: 0x04 });
+    assert_eq!(board.pins[2].reported_value, 1);
+
+    board.set_pin_mode(14, PinMode::Analog).unwrap();
+    board.connection.set_analog_input(14, 512);
+    board.connection.sample();
+
+    let message = read_until(&mut board, |m| match *m {
+        FirmataMessage::AnalogMessage { value, .. } => value != 0,
+        _ => false,
+    });
+    assert_eq!(message, FirmataMessage::AnalogMessage { channel: 0, value: 512 });
+    assert!(board.connection.is_reporting_analog(0));
+}
+
+#[test]
+fn analog_reporting_follows_pin_mode() {
+    let mut board = uno();
+
+    board.set_pin_mode(14, PinMode::Analog).unwrap();
+    assert!(board.connection.is_reporting_analog(0));
+    assert_eq!(board.reporting_channels(), vec![0]);
+
+    board.disable_reporting(14).unwrap();
+    assert!(!board.connection.is_reporting_analog(0));
+    assert!(board.reporting_channels().is_empty());
+}
+
+#[test]
+fn servo_config_stops_analog_reporting() {
+    let mut profile = Profile::uno();
+    profile.pins[14].push(Mode { mode: PinMode::Servo, resolution: 14 });
+    let mut board = Board::new(Box::new(Simulator::new(profile))).unwrap();
+
+    board.set_pin_mode(14, PinMode::Analog).unwrap();
+    board.servo_config(14, 544, 2400).unwrap();
+    assert!(!board.connection.is_reporting_analog(0));
+    assert!(board.reporting_channels().is_empty());
+}
+
+#[test]
+fn fresh_analog_pins_report_their_channel() {
+    let mut board = uno();
+
+    board.enable_reporting(15).unwrap();
+    assert!(board.connection.is_reporting_analog(1));
+    assert_eq!(board.reporting_channels(), vec![1]);
+}
+
+#[test]
+fn i2c_devices_answer_reads() {
+    let mut memory = I2CMemory::new();
+    memory.set_register(0x10, 0xAB);
+    let mut simulator = Simulator::uno();
+    simulator.attach_i2c_device(0x50, memory);
+    let mut board = Board::new(Box::new(simulator)).unwrap();
+
+    board.i2c_write(0x50, &[0x11, 0xCD]).unwrap();
+    let reply = board.i2c_read_reply(0x50, Some(0x10), 2, Duration::from_millis(100)).unwrap();
+    assert_eq!(reply.data, vec![0xAB, 0xCD]);
+
+    board.i2c_request(&I2CRequest::read_continuously(0x50, 1).register(0x11)).unwrap();
+    board.connection.sample();
+    let reply = board.i2c_read_reply(0x50, Some(0x11), 1, Duration::from_millis(100)).unwrap();
+    assert_eq!(reply.data, vec![0xCD]);
+}
+
+#[test]
+fn strings_and_sampling_interval() {
+    let mut board = uno();
+    board.send_string("hello").unwrap();
+    board.set_sampling_interval(Duration::from_millis(100)).unwrap();
+    board.connection.send_string("ready");
+
+    read_until(&mut board, |m| *m == FirmataMessage::StringData("ready".to_string()));
+    assert_eq!(board.connection.received_strings(), ["hello".to_string()]);
+    assert_eq!(board.connection.sampling_interval(), Duration::from_millis(100));
+    assert_eq!(board.string_data, vec!["ready".to_string()]);
+}
+
+#[test]
+fn stepper_moves_complete() {
+    let mut profile = Profile::uno();
+    for modes in profile.pins.iter_mut().skip(2) {
+        modes.push(Mode { mode: PinMode::Stepper, resolution: 1 });
+    }
+    let mut board = Board::new(Box::new(Simulator::new(profile))).unwrap();
+    let interface = StepperInterface::FourWire { motor_pins: [8, 9, 10, 11] };
+
+    board.stepper_config(1, interface, 200).unwrap();
+    board.stepper_step(&StepperMove::new(1, StepDirection::Clockwise, 300, 500)).unwrap();
+    board.stepper_wait(1, Duration::from_millis(100)).unwrap();
+    let step = StepperMove::new(1, StepDirection::CounterClockwise, 100, 500).ramp(50, 50);
+    board.stepper_step(&step).unwrap();
+    board.stepper_wait(1, Duration::from_millis(100)).unwrap();
+
+    assert_eq!(board.connection.stepper_position(1), Some(200));
+    assert_eq!(board.connection.pins()[11].mode, PinMode::Stepper);
+}